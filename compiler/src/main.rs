@@ -8,8 +8,8 @@
 #![allow(clippy::missing_errors_doc)]
 
 use clap::Parser;
-use parser::parse_ceceo;
-use std::fs;
+use parser::{diagnostics::render_parse_error, parse_ceceo};
+use std::{fs, process};
 
 #[derive(Parser, Default, Debug)]
 struct Arguments {
@@ -18,11 +18,14 @@ struct Arguments {
 
 fn main() {
     let args = Arguments::parse();
-    match fs::read_to_string(args.file_name) {
-        Ok(contents) => {
-            let parsed_ceceo = parse_ceceo(&contents).unwrap();
-            println!("{parsed_ceceo:?}");
-        }
+    match fs::read_to_string(&args.file_name) {
+        Ok(contents) => match parse_ceceo(&contents) {
+            Ok(parsed_ceceo) => println!("{parsed_ceceo:?}"),
+            Err(err) => {
+                eprintln!("{}", render_parse_error(&args.file_name, &contents, &err));
+                process::exit(1);
+            }
+        },
         Err(err) => {
             println!("{err}");
         }
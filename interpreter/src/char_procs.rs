@@ -0,0 +1,41 @@
+use bimap::BiHashMap;
+use std::sync::LazyLock;
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub enum CharProcs {
+    IsChar,
+    CharToInteger,
+    IntegerToChar,
+    Upcase,
+    Downcase,
+    IsAlphabetic,
+    IsNumeric,
+    IsWhitespace,
+    CharEq,
+    StringRef,
+}
+
+static CHAR_PROCS_MAP: LazyLock<BiHashMap<CharProcs, &'static str>> = LazyLock::new(|| {
+    BiHashMap::from_iter([
+        (CharProcs::IsChar, "char?"),
+        (CharProcs::CharToInteger, "char->integer"),
+        (CharProcs::IntegerToChar, "integer->char"),
+        (CharProcs::Upcase, "char-upcase"),
+        (CharProcs::Downcase, "char-downcase"),
+        (CharProcs::IsAlphabetic, "char-alphabetic?"),
+        (CharProcs::IsNumeric, "char-numeric?"),
+        (CharProcs::IsWhitespace, "char-whitespace?"),
+        (CharProcs::CharEq, "char=?"),
+        (CharProcs::StringRef, "string-ref"),
+    ])
+});
+
+impl<'a> TryFrom<&'a str> for CharProcs {
+    type Error = &'static str;
+    fn try_from(c: &'a str) -> Result<Self, Self::Error> {
+        CHAR_PROCS_MAP
+            .get_by_right(c)
+            .cloned()
+            .ok_or("Unknown operator")
+    }
+}
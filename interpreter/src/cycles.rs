@@ -0,0 +1,105 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    environment::Environment,
+    eval_result::{EvalResult, SharedVec},
+};
+
+/// A value that other values share, and so can be part of a reference
+/// cycle: a procedure bound in the frame it keeps, or a vector holding
+/// itself.
+#[derive(Clone)]
+enum Shared {
+    Frame(Environment),
+    Vector(SharedVec<EvalResult>),
+}
+
+impl Shared {
+    fn id(&self) -> usize {
+        match self {
+            Self::Frame(env) => env.id(),
+            Self::Vector(items) => items.id(),
+        }
+    }
+
+    fn strong_count(&self) -> usize {
+        match self {
+            Self::Frame(env) => env.strong_count(),
+            Self::Vector(items) => items.strong_count(),
+        }
+    }
+
+    /// The frames and vectors this one holds. The native procedures are
+    /// opaque, so what they hold isn't followed.
+    fn children(&self) -> Vec<Self> {
+        let (parent, values) = match self {
+            Self::Frame(env) => (env.parent().cloned(), env.values()),
+            Self::Vector(items) => (None, items.read().clone()),
+        };
+
+        let held = values.into_iter().filter_map(|value| match value {
+            EvalResult::Proc(proc) => Some(Self::Frame(proc.get_env().clone())),
+            EvalResult::Vector(items) => Some(Self::Vector(items)),
+            _ => None,
+        });
+        return parent.map(Shared::Frame).into_iter().chain(held).collect();
+    }
+
+    fn clear(&self) {
+        match self {
+            Self::Frame(env) => env.clear(),
+            Self::Vector(items) => drop(std::mem::take(&mut *items.write())),
+        }
+    }
+}
+
+/// Frees the reference cycles among the frames and vectors reachable from
+/// `global`, the environment of an interpreter being dropped, which keeps
+/// one more copy of it.
+///
+/// The values held from outside, like a procedure the host kept, are
+/// copies that aren't counted from the values reachable here. They stay as
+/// they are, along with everything they reach, and the rest is cleared.
+pub fn free_cycles(global: &Environment) {
+    let global_id = global.id();
+    let mut nodes = HashMap::new();
+    let mut edges = HashMap::new();
+    let mut pending = vec![Shared::Frame(global.clone())];
+    while let Some(node) = pending.pop() {
+        let id = node.id();
+        if nodes.contains_key(&id) {
+            continue;
+        }
+
+        let children = node.children();
+        edges.insert(id, children.iter().map(Shared::id).collect::<Vec<_>>());
+        nodes.insert(id, node);
+        pending.extend(children);
+    }
+
+    let mut internal = HashMap::<usize, usize>::new();
+    for id in edges.values().flatten() {
+        *internal.entry(*id).or_default() += 1;
+    }
+
+    // Besides the copies counted, there's the one in `nodes`, and the
+    // interpreter's for the global environment
+    let held_outside = |id: &usize| {
+        let known = 1 + internal.get(id).copied().unwrap_or(0) + usize::from(*id == global_id);
+        nodes[id].strong_count() > known
+    };
+
+    let mut live = HashSet::new();
+    let mut pending: Vec<usize> = nodes.keys().copied().filter(held_outside).collect();
+    while let Some(id) = pending.pop() {
+        if live.insert(id) {
+            pending.extend(&edges[&id]);
+        }
+    }
+
+    for (id, node) in &nodes {
+        if !live.contains(id) {
+            node.clear();
+        }
+    }
+}
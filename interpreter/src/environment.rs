@@ -0,0 +1,119 @@
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    sync::{Arc, RwLock},
+};
+
+use crate::{eval_result::EvalResult, interpreter::Options};
+
+/// The variables visible at some point of a program: a frame of bindings,
+/// nested in the environment it was created in. Copies share the frame, so
+/// a procedure that keeps the environment it was defined in sees the
+/// definitions made there later, like its own name when it's recursive.
+#[derive(Clone)]
+pub struct Environment(Arc<Frame>);
+
+struct Frame {
+    bindings: RwLock<HashMap<String, EvalResult>>,
+    parent: Option<Environment>,
+    options: Arc<Options>,
+}
+
+impl Environment {
+    /// An empty top-level environment
+    pub fn new() -> Self {
+        Self::with_options(Options::default())
+    }
+
+    /// An empty top-level environment for an interpreter set up with
+    /// `options`, which the frames nested in it share.
+    pub fn with_options(options: Options) -> Self {
+        Self(Arc::new(Frame {
+            bindings: RwLock::new(HashMap::new()),
+            parent: None,
+            options: Arc::new(options),
+        }))
+    }
+
+    /// A new, empty frame nested in this environment, for the arguments of
+    /// a procedure call.
+    pub fn extend(&self) -> Self {
+        Self(Arc::new(Frame {
+            bindings: RwLock::new(HashMap::new()),
+            parent: Some(self.clone()),
+            options: self.0.options.clone(),
+        }))
+    }
+
+    pub fn options(&self) -> &Options {
+        &self.0.options
+    }
+
+    /// Binds `name` in the innermost frame, replacing its previous value
+    /// there and hiding the ones in the outer frames.
+    pub fn define(&self, name: impl Into<String>, value: EvalResult) {
+        self.0.bindings.write().unwrap().insert(name.into(), value);
+    }
+
+    /// The value of `name` in the innermost frame that binds it
+    pub fn lookup(&self, name: &str) -> Option<EvalResult> {
+        let mut env = self;
+        loop {
+            if let Some(value) = env.0.bindings.read().unwrap().get(name) {
+                return Some(value.clone());
+            }
+
+            env = env.0.parent.as_ref()?;
+        }
+    }
+
+    /// The environment this one is nested in
+    pub(crate) fn parent(&self) -> Option<&Self> {
+        self.0.parent.as_ref()
+    }
+
+    /// The values bound in this frame
+    pub(crate) fn values(&self) -> Vec<EvalResult> {
+        self.0.bindings.read().unwrap().values().cloned().collect()
+    }
+
+    /// Tells the frame apart from the others, as long as it's alive
+    pub(crate) fn id(&self) -> usize {
+        Arc::as_ptr(&self.0) as usize
+    }
+
+    /// How many copies of the environment there are
+    pub(crate) fn strong_count(&self) -> usize {
+        Arc::strong_count(&self.0)
+    }
+
+    /// Drops the bindings of this frame, but not of the outer ones
+    pub(crate) fn clear(&self) {
+        let bindings = std::mem::take(&mut *self.0.bindings.write().unwrap());
+        drop(bindings);
+    }
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PartialEq for Environment {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+// A procedure is usually bound in the environment it keeps, so only the
+// names are printed to avoid going around in circles
+impl Debug for Environment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let bindings = self.0.bindings.read().unwrap();
+        f.debug_struct("Environment")
+            .field("names", &bindings.keys().collect::<Vec<_>>())
+            .field("parent", &self.0.parent)
+            .finish()
+    }
+}
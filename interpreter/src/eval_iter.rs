@@ -1,41 +1,57 @@
 use parser::ast::{Atom, Node};
 
 use crate::{
+    environment::Environment,
     eval_result::EvalResult,
-    expr_interpreter::{eval_list, DEFINITIONS_MAP},
+    expr_interpreter::{eval_list, is_builtin},
+    quasiquote::eval_quasiquote,
+    runtime_error::{ErrorKind, RuntimeError},
 };
 
-pub fn eval_atom(atom: &Atom) -> EvalResult {
-    if let Atom::Symbol(sym) = atom {
-        let def_map = DEFINITIONS_MAP.read().unwrap();
-        let def = def_map.get(sym);
+pub fn eval_atom(atom: &Atom, env: &Environment) -> Result<EvalResult, RuntimeError> {
+    let Atom::Symbol(sym) = atom else {
+        return Ok(EvalResult::Atom(atom.clone()));
+    };
 
-        match def {
-            Some(node) => {
-                return eval_node(node);
-            }
-            None => (),
-        }
+    if let Some(value) = env.lookup(sym) {
+        return Ok(value);
+    }
+    // The name of a built-in stands for the procedure, so it can be passed
+    // around like the ones defined in the program
+    if is_builtin(sym) {
+        return Ok(EvalResult::Atom(atom.clone()));
     }
 
-    EvalResult::Atom(atom.clone())
+    Err(ErrorKind::UnboundVariable(sym.clone()).into())
 }
 
-pub fn eval_node(node: &Node) -> EvalResult {
+pub fn eval_node(node: &Node, env: &Environment) -> Result<EvalResult, RuntimeError> {
     return match node {
-        Node::Atom(a) => eval_atom(a),
-        Node::List(l) => eval_list(l),
-        Node::QuoteList(ql) => EvalResult::QuoteList(ql.clone()),
-        Node::QuoteAtom(qa) => EvalResult::QuoteAtom(qa.clone()),
+        Node::Atom(a) => eval_atom(a, env),
+        Node::List(l) => eval_list(l, env),
+        Node::QuoteList(ql) => Ok(EvalResult::QuoteList(ql.clone())),
+        Node::QuoteAtom(qa) => Ok(EvalResult::QuoteAtom(qa.clone())),
+        Node::QuoteDottedList(ql, tail) => {
+            Ok(EvalResult::QuoteDottedList(ql.clone(), tail.clone()))
+        }
+        Node::Vector(_) | Node::Bytevector(_) => Ok(EvalResult::from_datum(node)),
+        Node::DottedList(..) => Err(RuntimeError::bad_syntax(format!(
+            "Improper list can't be evaluated: {node}"
+        ))),
+        Node::Quasiquote(template) => eval_quasiquote(template, env),
+        Node::Unquote(_) | Node::UnquoteSplicing(_) => Err(RuntimeError::bad_syntax(format!(
+            "Unquote outside of a quasiquote: {node}"
+        ))),
     };
 }
 
 pub struct NodeIter<'a> {
     internal_node_slice: &'a [Node],
+    env: &'a Environment,
 }
 
 impl Iterator for NodeIter<'_> {
-    type Item = EvalResult;
+    type Item = Result<EvalResult, RuntimeError>;
 
     fn next(&mut self) -> Option<Self::Item> {
         let next = self.internal_node_slice.first();
@@ -43,7 +59,7 @@ impl Iterator for NodeIter<'_> {
             Some(next) => {
                 self.internal_node_slice = &self.internal_node_slice[1..];
 
-                return Some(eval_node(next));
+                return Some(eval_node(next, self.env));
             }
             None => None,
         }
@@ -51,13 +67,14 @@ impl Iterator for NodeIter<'_> {
 }
 
 pub trait EvalIter {
-    fn iter_eval(&self) -> NodeIter;
+    fn iter_eval<'a>(&'a self, env: &'a Environment) -> NodeIter<'a>;
 }
 
 impl EvalIter for &[Node] {
-    fn iter_eval(&self) -> NodeIter {
+    fn iter_eval<'a>(&'a self, env: &'a Environment) -> NodeIter<'a> {
         return NodeIter {
-            internal_node_slice: &self,
+            internal_node_slice: self,
+            env,
         };
     }
 }
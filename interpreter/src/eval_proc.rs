@@ -1,37 +1,54 @@
-use parser::ast::{Atom, Node};
+use parser::{
+    ast::{Atom, Node},
+    number::Number,
+};
 
-use crate::{eval_iter::EvalIter, eval_result::EvalResult};
+use crate::{
+    environment::Environment,
+    eval_iter::{eval_node, EvalIter},
+    eval_result::EvalResult,
+    runtime_error::RuntimeError,
+};
 
 pub trait EvalProc<T> {
-    fn eval_proc(&self, f: impl Fn(T, T) -> T) -> T;
+    fn eval_proc(&self, env: &Environment, f: impl Fn(T, T) -> T) -> Result<T, RuntimeError>;
 }
 
-impl EvalProc<i32> for &[Node] {
-    fn eval_proc(&self, reducer: impl Fn(i32, i32) -> i32) -> i32 {
-        self.iter_eval()
-            .map(|er| {
-                if let EvalResult::Atom(ref atom) = &er && let Atom::Num(num) = atom {
-                return *num;
-            } else {
-                panic!("Incorrect type: Expected number, got {er:?}");
-            }
-            })
-            .reduce(reducer)
-            .unwrap()
+/// Evaluates each node, which must give a number
+pub fn iter_numbers<'a>(
+    node_slice: &'a [Node],
+    env: &'a Environment,
+) -> impl Iterator<Item = Result<Number, RuntimeError>> + 'a {
+    node_slice.iter().map(|node| match eval_node(node, env)? {
+        EvalResult::Atom(Atom::Num(num)) => Ok(num),
+        er => Err(RuntimeError::type_error("number", er)),
+    })
+}
+
+impl EvalProc<Number> for &[Node] {
+    fn eval_proc(
+        &self,
+        env: &Environment,
+        reducer: impl Fn(Number, Number) -> Number,
+    ) -> Result<Number, RuntimeError> {
+        let nums = iter_numbers(self, env).collect::<Result<Vec<_>, _>>()?;
+        Ok(nums.into_iter().reduce(reducer).unwrap())
     }
 }
 
 impl EvalProc<String> for &[Node] {
-    fn eval_proc(&self, reducer: impl Fn(String, String) -> String) -> String {
-        self.iter_eval()
-            .map(|er| {
-                if let EvalResult::Atom(atom) = er && let Atom::Str(st) = atom {
-                return st;
-            } else {
-                panic!("Incorrect type: Expected string");
-            }
+    fn eval_proc(
+        &self,
+        env: &Environment,
+        reducer: impl Fn(String, String) -> String,
+    ) -> Result<String, RuntimeError> {
+        let strings = self
+            .iter_eval(env)
+            .map(|er| match er? {
+                EvalResult::Atom(Atom::Str(st)) => Ok(st),
+                er => Err(RuntimeError::type_error("string", er)),
             })
-            .reduce(reducer)
-            .unwrap()
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(strings.into_iter().reduce(reducer).unwrap_or_default())
     }
 }
@@ -1,15 +1,163 @@
-use crate::{eval_iter::eval_node, user_proc::UserProc};
+use crate::{native_proc::NativeProc, runtime_error::RuntimeError, user_proc::UserProc};
 use parser::ast::{Atom, Node};
+use std::cell::RefCell;
 use std::collections::hash_map::DefaultHasher;
-use std::fmt::Display;
+use std::fmt::{Debug, Display};
 use std::hash::{Hash, Hasher};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum EvalResult {
     Atom(Atom),
     QuoteAtom(Atom),
     QuoteList(Vec<Node>),
+    QuoteDottedList(Vec<Node>, Box<Node>),
+    Vector(SharedVec<Self>),
+    Bytevector(SharedVec<u8>),
     Proc(UserProc),
+    Native(NativeProc),
+}
+
+/// The items of a vector value. Copies of the value share them, so a
+/// `vector-set!` through one copy is seen by all of them.
+#[derive(Clone)]
+pub struct SharedVec<T>(Arc<RwLock<Vec<T>>>);
+
+impl<T> SharedVec<T> {
+    #[must_use]
+    pub fn new(items: Vec<T>) -> Self {
+        Self(Arc::new(RwLock::new(items)))
+    }
+
+    /// # Panics
+    ///
+    /// If a thread panicked while changing the items
+    pub fn read(&self) -> RwLockReadGuard<'_, Vec<T>> {
+        self.0.read().unwrap()
+    }
+
+    /// # Panics
+    ///
+    /// If a thread panicked while changing the items
+    pub fn write(&self) -> RwLockWriteGuard<'_, Vec<T>> {
+        self.0.write().unwrap()
+    }
+
+    /// Tells the items apart from the ones of other vectors, as long as
+    /// they're alive
+    pub(crate) fn id(&self) -> usize {
+        Arc::as_ptr(&self.0) as usize
+    }
+
+    /// How many copies of the vector there are
+    pub(crate) fn strong_count(&self) -> usize {
+        Arc::strong_count(&self.0)
+    }
+}
+
+thread_local! {
+    /// The pairs of vectors being compared further up the stack
+    static COMPARING: RefCell<Vec<(usize, usize)>> = const { RefCell::new(Vec::new()) };
+    /// The vectors being debug printed further up the stack
+    static DEBUGGING: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+impl<T: Debug> Debug for SharedVec<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let id = self.id();
+        if DEBUGGING.with(|debugging| debugging.borrow().contains(&id)) {
+            return write!(f, "SharedVec(<cycle>)");
+        }
+
+        DEBUGGING.with(|debugging| debugging.borrow_mut().push(id));
+        let result = f.debug_tuple("SharedVec").field(&*self.read()).finish();
+        DEBUGGING.with(|debugging| debugging.borrow_mut().pop());
+        return result;
+    }
+}
+
+impl<T: PartialEq> PartialEq for SharedVec<T> {
+    fn eq(&self, other: &Self) -> bool {
+        if Arc::ptr_eq(&self.0, &other.0) {
+            return true;
+        }
+
+        // Vectors that hold themselves meet again inside, where they're
+        // taken as equal, so that they are if the rest of their items are
+        let pair = (self.id(), other.id());
+        if COMPARING.with(|comparing| comparing.borrow().contains(&pair)) {
+            return true;
+        }
+
+        COMPARING.with(|comparing| comparing.borrow_mut().push(pair));
+        let equal = *self.read() == *other.read();
+        COMPARING.with(|comparing| comparing.borrow_mut().pop());
+        return equal;
+    }
+}
+
+impl EvalResult {
+    /// The value of `datum` written as data, like an item of a quoted list
+    /// or vector literal. Symbols stay symbols, other atoms are themselves.
+    pub fn from_datum(datum: &Node) -> Self {
+        let tagged = |tag: &str, node: Node| {
+            Self::QuoteList(vec![Node::Atom(Atom::Symbol(tag.to_string())), node])
+        };
+
+        match datum {
+            Node::Atom(atom @ Atom::Symbol(_)) => Self::QuoteAtom(atom.clone()),
+            Node::Atom(atom) => Self::Atom(atom.clone()),
+            Node::List(list) => Self::QuoteList(list.clone()),
+            Node::DottedList(list, tail) => Self::QuoteDottedList(list.clone(), tail.clone()),
+            Node::Vector(items) => {
+                Self::Vector(SharedVec::new(items.iter().map(Self::from_datum).collect()))
+            }
+            Node::Bytevector(bytes) => Self::Bytevector(SharedVec::new(bytes.clone())),
+            Node::QuoteAtom(atom) => tagged("quote", Node::Atom(atom.clone())),
+            Node::QuoteList(list) => tagged("quote", Node::List(list.clone())),
+            Node::QuoteDottedList(list, tail) => {
+                tagged("quote", Node::DottedList(list.clone(), tail.clone()))
+            }
+            Node::Quasiquote(inner) => tagged("quasiquote", *inner.clone()),
+            Node::Unquote(inner) => tagged("unquote", *inner.clone()),
+            Node::UnquoteSplicing(inner) => tagged("unquote-splicing", *inner.clone()),
+        }
+    }
+
+    /// Turns the value back into the datum it stands for, so it can be
+    /// placed inside a quoted list. Procedures and vectors that hold
+    /// themselves have no datum.
+    pub fn into_datum(self) -> Result<Node, RuntimeError> {
+        return self.into_datum_within(&mut Vec::new());
+    }
+
+    /// `into_datum` for a value inside the vectors in `open`
+    fn into_datum_within(self, open: &mut Vec<usize>) -> Result<Node, RuntimeError> {
+        let datum = match self {
+            Self::Atom(atom) | Self::QuoteAtom(atom) => Node::Atom(atom),
+            Self::QuoteList(list) => Node::List(list),
+            Self::QuoteDottedList(list, tail) => Node::DottedList(list, tail),
+            Self::Vector(items) if open.contains(&items.id()) => {
+                return Err(RuntimeError::type_error("datum", Self::Vector(items)))
+            }
+            Self::Vector(items) => {
+                open.push(items.id());
+                let datums: Result<_, _> = items
+                    .read()
+                    .iter()
+                    .map(|item| item.clone().into_datum_within(open))
+                    .collect();
+                open.pop();
+                Node::Vector(datums?)
+            }
+            Self::Bytevector(bytes) => Node::Bytevector(bytes.read().clone()),
+            proc @ (Self::Proc(_) | Self::Native(_)) => {
+                return Err(RuntimeError::type_error("datum", proc))
+            }
+        };
+
+        Ok(datum)
+    }
 }
 
 fn calculate_hash<T: Hash>(t: &T) -> u64 {
@@ -18,27 +166,104 @@ fn calculate_hash<T: Hash>(t: &T) -> u64 {
     s.finish()
 }
 
+/// The vectors in a value that hold themselves, which are written with
+/// datum labels, `#0=#(1 #0#)`, instead of going around forever
+struct Labels {
+    cyclic: Vec<usize>,
+    written: Vec<usize>,
+}
+
+impl EvalResult {
+    /// Adds to `cyclic` the vectors met again inside themselves, with
+    /// `open` the vectors this value is inside of
+    fn find_cycles(&self, open: &mut Vec<usize>, cyclic: &mut Vec<usize>) {
+        let Self::Vector(items) = self else {
+            return;
+        };
+
+        let id = items.id();
+        if open.contains(&id) {
+            if !cyclic.contains(&id) {
+                cyclic.push(id);
+            }
+            return;
+        }
+
+        open.push(id);
+        for item in items.read().iter() {
+            item.find_cycles(open, cyclic);
+        }
+        open.pop();
+    }
+
+    fn write_labelled(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        labels: &mut Labels,
+    ) -> std::fmt::Result {
+        let Self::Vector(items) = self else {
+            return write!(f, "{self}");
+        };
+
+        let id = items.id();
+        if let Some(label) = labels.written.iter().position(|written| *written == id) {
+            return write!(f, "#{label}#");
+        }
+
+        if labels.cyclic.contains(&id) {
+            write!(f, "#{}=", labels.written.len())?;
+            labels.written.push(id);
+        }
+
+        write!(f, "#(")?;
+        for (idx, item) in items.read().iter().enumerate() {
+            if idx > 0 {
+                write!(f, " ")?;
+            }
+            item.write_labelled(f, labels)?;
+        }
+        return write!(f, ")");
+    }
+}
+
 impl Display for EvalResult {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            EvalResult::Atom(a) | EvalResult::QuoteAtom(a) => match a {
+            Self::Atom(a) | Self::QuoteAtom(a) => match a {
                 Atom::Num(n) => write!(f, "{n}"),
                 Atom::Symbol(s) => write!(f, "{s}"),
                 Atom::Str(str) => write!(f, "{str}"),
                 Atom::Bool(b) => write!(f, "{b}"),
+                Atom::Char(c) => write!(f, "{c}"),
             },
-            EvalResult::QuoteList(ql) => {
+            Self::QuoteList(ql) => ql
+                .first()
+                .map_or(Ok(()), |node| write!(f, "{}", Self::from_datum(node))),
+            Self::QuoteDottedList(ql, tail) => {
+                write!(f, "(")?;
                 for node in ql {
-                    let node_eval = eval_node(node);
-                    return write!(f, "{node_eval}");
+                    write!(f, "{node} ")?;
                 }
-
-                Ok(())
+                write!(f, ". {tail})")
+            }
+            Self::Vector(_) => {
+                let mut cyclic = Vec::new();
+                self.find_cycles(&mut Vec::new(), &mut cyclic);
+                let mut labels = Labels {
+                    cyclic,
+                    written: Vec::new(),
+                };
+                self.write_labelled(f, &mut labels)
+            }
+            Self::Bytevector(bytes) => {
+                let bytes: Vec<String> = bytes.read().iter().map(u8::to_string).collect();
+                write!(f, "#u8({})", bytes.join(" "))
             }
-            EvalResult::Proc(p) => {
+            Self::Proc(p) => {
                 let h = calculate_hash(p);
                 write!(f, "procedure:{h}")
             }
+            Self::Native(native) => write!(f, "procedure:{}", native.get_name()),
         }
     }
 }
@@ -1,135 +1,192 @@
-use std::{
-    collections::HashMap,
-    sync::{Arc, LazyLock, RwLock},
-};
-
 use crate::{
-    debug_print,
+    char_procs::CharProcs,
+    environment::Environment,
     eval_iter::eval_node,
     eval_result::EvalResult,
     generic_procs::GenericProcs,
+    native_proc::NativeProc,
     numeric_procs::NumericProcs,
     procs_impl::{evaluate_and_return_last, ProcImpls},
+    runtime_error::{Arity, ErrorKind, RuntimeError},
     string_procs::StringProcs,
     user_proc::UserProc,
+    vector_procs::VectorProcs,
 };
 use parser::ast::{Atom, Node};
 
 // const OPS: [char; 11] = ['+', '-', '*', '<', '>', '%', '\"', '=', '!', '&', '/'];
-const INVALID_PROC: &str = "Invalid procedure expression";
-pub static DEFINITIONS_MAP: LazyLock<Arc<RwLock<HashMap<String, Node>>>> =
-    LazyLock::new(|| Arc::new(RwLock::new(HashMap::new())));
 
-fn eval_proc<'a>(c: &str, node_args: &'a [Node]) -> EvalResult {
+fn debug_print(env: &Environment, log: &str) {
+    if !env.options().debug {
+        return;
+    }
+
+    println!("DEBUG: {log}");
+}
+
+/// Whether `name` is one of the procedures built into the interpreter
+pub fn is_builtin(name: &str) -> bool {
+    NumericProcs::try_from(name).is_ok()
+        || StringProcs::try_from(name).is_ok()
+        || GenericProcs::try_from(name).is_ok()
+        || CharProcs::try_from(name).is_ok()
+        || VectorProcs::try_from(name).is_ok()
+}
+
+fn eval_proc(c: &str, node_args: &[Node], env: &Environment) -> Result<EvalResult, RuntimeError> {
+    // Definitions hide the built-in procedures with the same name
+    match env.lookup(c) {
+        Some(EvalResult::Proc(proc)) => eval_lambda(&proc, node_args, env),
+        Some(EvalResult::Native(native)) => eval_native(&native, node_args, env),
+        // Bound to a built-in, like `f` in `((lambda (f) (f 1 2)) +)`
+        Some(EvalResult::Atom(Atom::Symbol(name))) => eval_builtin(&name, node_args, env),
+        Some(value) => Err(ErrorKind::NotAProcedure(value.to_string()).into()),
+        None => eval_builtin(c, node_args, env),
+    }
+}
+
+fn eval_builtin(
+    c: &str,
+    node_args: &[Node],
+    env: &Environment,
+) -> Result<EvalResult, RuntimeError> {
     if let Ok(nproc) = NumericProcs::try_from(c) {
-        return EvalResult::Atom(eval_numeric_proc(nproc, node_args));
+        return Ok(EvalResult::Atom(eval_numeric_proc(nproc, node_args, env)?));
     } else if let Ok(sproc) = StringProcs::try_from(c) {
-        return EvalResult::Atom(eval_string_proc(sproc, node_args));
+        return Ok(EvalResult::Atom(eval_string_proc(sproc, node_args, env)?));
     } else if let Ok(gproc) = GenericProcs::try_from(c) {
-        return eval_generic_proc(gproc, node_args);
-    } else {
-        let def_map = DEFINITIONS_MAP.read().unwrap();
-        let proc_opt = def_map.get(c);
-
-        if let Some(proc_node) = proc_opt
-        && let EvalResult::Proc(proc) = eval_node(proc_node) {
-            return eval_lambda(proc, node_args);
-        }
+        return eval_generic_proc(gproc, node_args, env);
+    } else if let Ok(cproc) = CharProcs::try_from(c) {
+        return eval_char_proc(cproc, node_args, env);
+    } else if let Ok(vproc) = VectorProcs::try_from(c) {
+        return eval_vector_proc(vproc, node_args, env);
     }
 
-    panic!("{INVALID_PROC}: {c}");
+    Err(ErrorKind::UnboundVariable(c.to_string()).into())
 }
 
-fn eval_numeric_proc(proc: NumericProcs, node_args: &[Node]) -> Atom {
-    let result = node_args.perform_proc(proc);
-    debug_print(&std::format!("{result}"));
-    return Atom::Num(result);
+fn eval_numeric_proc(
+    proc: NumericProcs,
+    node_args: &[Node],
+    env: &Environment,
+) -> Result<Atom, RuntimeError> {
+    let result = node_args.perform_proc(proc, env)?;
+    debug_print(env, &std::format!("{result}"));
+    return Ok(Atom::Num(result));
 }
 
-fn eval_string_proc(proc: StringProcs, node_args: &[Node]) -> Atom {
-    let result = node_args.perform_proc(proc);
-    debug_print(&std::format!("{result}"));
-    return Atom::Str(result);
+fn eval_string_proc(
+    proc: StringProcs,
+    node_args: &[Node],
+    env: &Environment,
+) -> Result<Atom, RuntimeError> {
+    let result = node_args.perform_proc(proc, env)?;
+    debug_print(env, &result);
+    return Ok(Atom::Str(result));
 }
 
-fn eval_generic_proc(proc: GenericProcs, node_args: &[Node]) -> EvalResult {
-    let result = node_args.perform_proc(proc);
-    debug_print(&std::format!("{result:?}"));
-    return result;
+fn eval_generic_proc(
+    proc: GenericProcs,
+    node_args: &[Node],
+    env: &Environment,
+) -> Result<EvalResult, RuntimeError> {
+    let result = node_args.perform_proc(proc, env)?;
+    debug_print(env, &std::format!("{result:?}"));
+    return Ok(result);
 }
 
-fn eval_with_proc_atom_and_args(proc_atom: Atom, arg_list: &[Node]) -> EvalResult {
-    if let Atom::Symbol(sym) = proc_atom {
-        return eval_proc(&sym, arg_list);
-    }
+fn eval_char_proc(
+    proc: CharProcs,
+    node_args: &[Node],
+    env: &Environment,
+) -> Result<EvalResult, RuntimeError> {
+    let result = node_args.perform_proc(proc, env)?;
+    debug_print(env, &std::format!("{result:?}"));
+    return Ok(result);
+}
 
-    panic!("{INVALID_PROC}");
+fn eval_vector_proc(
+    proc: VectorProcs,
+    node_args: &[Node],
+    env: &Environment,
+) -> Result<EvalResult, RuntimeError> {
+    let result = node_args.perform_proc(proc, env)?;
+    debug_print(env, &std::format!("{result:?}"));
+    return Ok(result);
 }
 
-fn eval_lambda(lambda: UserProc, arg_list: &[Node]) -> EvalResult {
-    fn replace_symbol_with_arg(
-        node: &Node,
-        arg_list: &[Node],
-        args: &HashMap<String, usize>,
-    ) -> Node {
-        match node {
-            Node::Atom(atom) => match atom {
-                Atom::Symbol(sym) => {
-                    if let Some(arg_idx) = args.get(sym) {
-                        return arg_list[*arg_idx].clone();
-                    } else {
-                        return node.clone();
-                    }
-                }
-                _ => node.clone(),
-            },
-            Node::List(list) => Node::List(
-                list.iter()
-                    .map(|node| replace_symbol_with_arg(node, arg_list, args))
-                    .collect::<Vec<Node>>(),
-            ),
-            _ => node.clone(),
-        }
+fn eval_lambda(
+    lambda: &UserProc,
+    arg_list: &[Node],
+    env: &Environment,
+) -> Result<EvalResult, RuntimeError> {
+    let arity = if lambda.is_variadic() {
+        Arity::AtLeast(lambda.get_arity())
+    } else {
+        Arity::Exactly(lambda.get_arity())
+    };
+    arity.check(arg_list)?;
+
+    // Each argument is evaluated once, in the environment of the call
+    let mut args: Vec<EvalResult> = arg_list
+        .iter()
+        .map(|node| eval_node(node, env))
+        .collect::<Result<_, _>>()?;
+    // The extra arguments are passed to the rest argument as a list
+    if lambda.is_variadic() {
+        let rest = args
+            .split_off(lambda.get_arity())
+            .into_iter()
+            .map(EvalResult::into_datum)
+            .collect::<Result<_, _>>()?;
+        args.push(EvalResult::QuoteList(rest));
     }
 
-    let expected_arg_count = lambda.get_arity();
-    let actual_arg_count = arg_list.len();
-    if actual_arg_count != expected_arg_count {
-        panic!("Arity mismatch: Expected {expected_arg_count}, got {actual_arg_count} instead");
+    // The body sees the arguments and, around them, the variables of the
+    // environment the procedure was created in
+    let frame = lambda.get_env().extend();
+    for (name, idx) in lambda.get_args() {
+        frame.define(name.clone(), args[*idx].clone());
     }
 
-    let args = lambda.get_args();
-    let body = lambda
-        .get_body()
-        .iter()
-        .map(|node| replace_symbol_with_arg(node, arg_list, args))
-        .collect::<Vec<Node>>();
+    evaluate_and_return_last(lambda.get_body(), &frame)?
+        .ok_or_else(|| RuntimeError::bad_syntax("Empty lambda body"))
+}
 
-    evaluate_and_return_last(&body).unwrap()
+fn eval_native(
+    native: &NativeProc,
+    arg_list: &[Node],
+    env: &Environment,
+) -> Result<EvalResult, RuntimeError> {
+    native.get_arity().check(arg_list)?;
+    let args = arg_list
+        .iter()
+        .map(|node| eval_node(node, env))
+        .collect::<Result<_, _>>()?;
+    let result = native.call(args)?;
+    debug_print(env, &std::format!("{result:?}"));
+    return Ok(result);
 }
 
-pub fn eval_list(list: &[Node]) -> EvalResult {
-    if list.is_empty() {
-        panic!("Missing procedure expression");
-    }
+pub fn eval_list(list: &[Node], env: &Environment) -> Result<EvalResult, RuntimeError> {
+    let Some((procedure, arg_list)) = list.split_first() else {
+        return Err(RuntimeError::bad_syntax("Missing procedure expression"));
+    };
 
-    let (procedure, arg_list) = list.split_first().unwrap();
-    match procedure {
-        Node::Atom(atom) | Node::QuoteAtom(atom) => {
-            eval_with_proc_atom_and_args(atom.clone(), arg_list)
+    let result = match procedure {
+        Node::Atom(Atom::Symbol(sym)) | Node::QuoteAtom(Atom::Symbol(sym)) => {
+            eval_proc(sym, arg_list, env)
         }
-        Node::List(list) => match eval_list(list) {
-            EvalResult::Atom(atom) => eval_with_proc_atom_and_args(atom, arg_list),
-            EvalResult::QuoteList(_) => panic!("{INVALID_PROC}"),
-            EvalResult::QuoteAtom(_) => todo!(),
-            EvalResult::Proc(proc) => eval_lambda(proc, arg_list),
+        Node::List(list) => match eval_list(list, env)? {
+            // A built-in passed around, a quoted symbol is only data
+            EvalResult::Atom(Atom::Symbol(sym)) => eval_builtin(&sym, arg_list, env),
+            EvalResult::Proc(proc) => eval_lambda(&proc, arg_list, env),
+            EvalResult::Native(native) => eval_native(&native, arg_list, env),
+            value => Err(ErrorKind::NotAProcedure(value.to_string()).into()),
         },
-        Node::QuoteList(_) => panic!("{INVALID_PROC}"),
-    }
-}
+        _ => Err(ErrorKind::NotAProcedure(procedure.to_string()).into()),
+    };
 
-pub fn interpret_ceceo(parsed_ceceo: Vec<Vec<Node>>) {
-    for expr in parsed_ceceo {
-        eval_list(&expr);
-    }
+    // Errors are reported in the innermost call they come from
+    result.map_err(|err| err.in_expr(|| Node::List(list.to_vec())))
 }
@@ -35,15 +35,9 @@ static GENERIC_PROCS_MAP: LazyLock<BiHashMap<GenericProcs, &'static str>> = Lazy
 impl<'a> TryFrom<&'a str> for GenericProcs {
     type Error = &'static str;
     fn try_from(c: &'a str) -> Result<Self, Self::Error> {
-        match GENERIC_PROCS_MAP.get_by_right(c) {
-            Some(gp) => Ok(gp.clone()),
-            None => Err("Unknown operator"),
-        }
-    }
-}
-
-impl<'a> From<GenericProcs> for &'a str {
-    fn from(val: GenericProcs) -> Self {
-        GENERIC_PROCS_MAP.get_by_left(&val).unwrap()
+        GENERIC_PROCS_MAP
+            .get_by_right(c)
+            .cloned()
+            .ok_or("Unknown operator")
     }
 }
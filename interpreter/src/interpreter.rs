@@ -0,0 +1,138 @@
+use std::fmt::Display;
+
+use parser::{ast::Node, diagnostics::Diagnostic, parse_ceceo};
+
+use crate::{
+    cycles,
+    environment::Environment,
+    eval_iter,
+    eval_result::EvalResult,
+    native_proc::{NativeFn, NativeProc},
+    runtime_error::RuntimeError,
+};
+
+/// Settings of an interpreter, seen by all the code it runs
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// Prints the result of every call to a built-in procedure
+    pub debug: bool,
+}
+
+/// A Ceceo interpreter that owns its global environment.
+///
+/// Programs run in different interpreters don't see each other's
+/// definitions, and interpreters can be used from different threads at the
+/// same time.
+#[derive(Debug, Default)]
+pub struct Interpreter {
+    env: Environment,
+}
+
+/// Parses `source` into the forms of a program
+pub fn parse(source: &str) -> Result<Vec<Node>, Diagnostic> {
+    parse_ceceo(source).map_err(|err| Diagnostic::from_parse_error(&err))
+}
+
+/// Why [`Interpreter::eval_str`] didn't return a value
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvalError {
+    Syntax(Diagnostic),
+    Runtime(RuntimeError),
+}
+
+impl Interpreter {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_options(Options::default())
+    }
+
+    #[must_use]
+    pub fn with_options(options: Options) -> Self {
+        Self {
+            env: Environment::with_options(options),
+        }
+    }
+
+    #[must_use]
+    pub fn options(&self) -> &Options {
+        self.env.options()
+    }
+
+    /// Parses `source` and evaluates its forms in order, returning the value
+    /// of the last one, or `None` if there are none.
+    pub fn eval_str(&self, source: &str) -> Result<Option<EvalResult>, EvalError> {
+        let program = parse(source).map_err(EvalError::Syntax)?;
+
+        let mut ret = None;
+        for node in &program {
+            ret = Some(self.eval_node(node)?);
+        }
+
+        return Ok(ret);
+    }
+
+    /// Evaluates `node` in the global environment
+    pub fn eval_node(&self, node: &Node) -> Result<EvalResult, RuntimeError> {
+        eval_iter::eval_node(node, &self.env)
+    }
+
+    /// Binds `name` in the global environment, like a top-level `define`
+    pub fn define(&self, name: impl Into<String>, value: EvalResult) {
+        self.env.define(name, value);
+    }
+
+    /// The value of `name` in the global environment
+    #[must_use]
+    pub fn lookup(&self, name: &str) -> Option<EvalResult> {
+        self.env.lookup(name)
+    }
+
+    /// Makes `native` callable by its name from the programs run here. Like
+    /// any global definition, it hides a built-in with the same name and is
+    /// replaced by a later `define`.
+    pub fn register(&self, native: NativeProc) {
+        self.define(native.get_name().to_string(), EvalResult::Native(native));
+    }
+
+    /// Registers `func` under `name`, see [`NativeProc::from_fn`]
+    pub fn register_fn<Args>(&self, name: impl Into<String>, func: impl NativeFn<Args>) {
+        self.register(NativeProc::from_fn(name, func));
+    }
+}
+
+// The procedures defined by the programs keep the frames they're bound in,
+// and vectors can hold themselves, so the cycles are broken for them to be
+// freed. What the host still holds is left untouched.
+impl Drop for Interpreter {
+    fn drop(&mut self) {
+        cycles::free_cycles(&self.env);
+    }
+}
+
+impl From<RuntimeError> for EvalError {
+    fn from(err: RuntimeError) -> Self {
+        Self::Runtime(err)
+    }
+}
+
+impl Display for EvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Syntax(diagnostic) => write!(
+                f,
+                "{} at bytes {}..{}",
+                diagnostic.message, diagnostic.span.start, diagnostic.span.end
+            ),
+            Self::Runtime(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for EvalError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Syntax(_) => None,
+            Self::Runtime(err) => Some(err),
+        }
+    }
+}
@@ -0,0 +1,59 @@
+#![feature(let_chains)]
+#![feature(once_cell)]
+#![feature(const_trait_impl)]
+#![deny(clippy::all)]
+#![warn(clippy::pedantic)]
+#![warn(clippy::nursery)]
+#![warn(clippy::cargo)]
+#![allow(clippy::needless_return)]
+#![allow(clippy::missing_docs_in_private_items)]
+#![allow(clippy::cargo_common_metadata)]
+#![allow(clippy::missing_errors_doc)]
+
+//! An interpreter for Ceceo that can be embedded in Rust programs.
+//!
+//! [`parse`] reads a program into [`Node`]s, and an [`Interpreter`]
+//! evaluates them, or evaluates source text with
+//! [`eval_str`](Interpreter::eval_str), into [`EvalResult`] values.
+//! Failures are [`EvalError`]s: a [`Diagnostic`] for a syntax error or a
+//! [`RuntimeError`] for an error found while evaluating.
+//!
+//! The host application can add its own procedures, written in Rust, with
+//! [`Interpreter::register_fn`].
+//!
+//! ```
+//! let interpreter = interpreter::Interpreter::new();
+//! interpreter.eval_str("(define square (lambda (x) (* x x)))").unwrap();
+//! let nine = interpreter.eval_str("(square 3)").unwrap();
+//! assert_eq!(nine.unwrap().to_string(), "9");
+//! ```
+
+mod char_procs;
+mod cycles;
+mod environment;
+mod eval_iter;
+mod eval_proc;
+mod eval_result;
+mod expr_interpreter;
+mod generic_procs;
+mod interpreter;
+mod native_proc;
+mod numeric_procs;
+mod procs_impl;
+mod quasiquote;
+mod runtime_error;
+mod string_procs;
+mod tests;
+mod user_proc;
+mod vector_procs;
+
+pub use eval_result::{EvalResult, SharedVec};
+pub use interpreter::{parse, EvalError, Interpreter, Options};
+pub use native_proc::{FromValue, IntoValue, NativeFn, NativeProc};
+pub use parser::{
+    ast::{Atom, Node},
+    diagnostics::Diagnostic,
+    number::Number,
+};
+pub use runtime_error::{Arity, ErrorKind, RuntimeError};
+pub use user_proc::UserProc;
@@ -1,6 +1,3 @@
-#![feature(let_chains)]
-#![feature(once_cell)]
-#![feature(const_trait_impl)]
 #![deny(clippy::all)]
 #![warn(clippy::pedantic)]
 #![warn(clippy::nursery)]
@@ -10,53 +7,170 @@
 #![allow(clippy::cargo_common_metadata)]
 #![allow(clippy::missing_errors_doc)]
 
-mod eval_iter;
-mod eval_proc;
-mod eval_result;
-pub mod expr_interpreter;
-mod generic_procs;
-mod numeric_procs;
-mod procs_impl;
-mod string_procs;
-mod tests;
-mod user_proc;
-
-use clap::Parser;
-use expr_interpreter::interpret_ceceo;
-use parser::parse_ceceo;
-use std::fs;
+use clap::{Parser, Subcommand};
+use interpreter::{EvalError, Interpreter, Options};
+use parser::{
+    cst,
+    diagnostics::render_parse_error,
+    format::{format_cst, DEFAULT_WIDTH},
+};
+use std::{fs, process};
 
 #[derive(Parser, Default, Debug)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Arguments {
-    file_name: String,
+    #[clap(subcommand)]
+    command: Option<Command>,
+    #[clap(required = true)]
+    file_name: Option<String>,
     #[clap(action, long)]
     debug: bool,
 }
 
-pub fn debug_print(log: &str) {
-    unsafe {
-        if !SHOULD_DEBUG {
-            return;
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Rewrites source files in the canonical style
+    Fmt {
+        #[clap(required = true)]
+        file_names: Vec<String>,
+        /// Only lists the files that aren't formatted, failing if there are any
+        #[clap(action, long)]
+        check: bool,
+        #[clap(long, default_value_t = DEFAULT_WIDTH)]
+        width: usize,
+    },
+}
+
+/// Formats each file, returning whether they all were already formatted
+/// (with `check`) or could be formatted (without it).
+fn format_files(file_names: &[String], check: bool, width: usize) -> bool {
+    let mut ok = true;
+    for file_name in file_names {
+        let contents = match fs::read_to_string(file_name) {
+            Ok(contents) => contents,
+            Err(err) => {
+                eprintln!("{file_name}: {err}");
+                ok = false;
+                continue;
+            }
+        };
+        let formatted = match cst::parse(&contents) {
+            Ok(cst) => format_cst(&cst, width),
+            Err(err) => {
+                eprintln!("{}", render_parse_error(file_name, &contents, &err));
+                ok = false;
+                continue;
+            }
+        };
+
+        if formatted == contents {
+            continue;
+        }
+        if check {
+            println!("{file_name} is not formatted");
+            ok = false;
+        } else if let Err(err) = fs::write(file_name, formatted) {
+            eprintln!("{file_name}: {err}");
+            ok = false;
         }
     }
 
-    println!("DEBUG: {log}");
+    return ok;
 }
 
-static mut SHOULD_DEBUG: bool = false;
+/// Runs the program in `file_name`, returning whether it ran without errors
+fn run_file(file_name: &str, debug: bool) -> bool {
+    let contents = match fs::read_to_string(file_name) {
+        Ok(contents) => contents,
+        Err(err) => {
+            eprintln!("{file_name}: {err}");
+            return false;
+        }
+    };
+
+    let interpreter = Interpreter::with_options(Options { debug });
+    match interpreter.eval_str(&contents) {
+        Ok(_) => true,
+        Err(EvalError::Syntax(diagnostic)) => {
+            eprintln!("{}", diagnostic.render(file_name, &contents));
+            false
+        }
+        Err(EvalError::Runtime(err)) => {
+            eprintln!("error: {err}");
+            false
+        }
+    }
+}
 
 fn main() {
     let args = Arguments::parse();
-    unsafe {
-        SHOULD_DEBUG = args.debug;
+    if let Some(Command::Fmt {
+        file_names,
+        check,
+        width,
+    }) = &args.command
+    {
+        let ok = format_files(file_names, *check, *width);
+        process::exit(i32::from(!ok));
     }
-    match fs::read_to_string(args.file_name) {
-        Ok(contents) => {
-            let parsed_ceceo = parse_ceceo(&contents).unwrap();
-            interpret_ceceo(parsed_ceceo);
-        }
-        Err(err) => {
-            println!("{err}");
+
+    let file_name = args.file_name.expect("required without a subcommand");
+    let ok = run_file(&file_name, args.debug);
+    process::exit(i32::from(!ok));
+}
+
+#[test]
+fn fmt_arguments_are_read() {
+    let args = Arguments::try_parse_from(["ceceo", "fmt", "--check", "--width", "40", "a.ceceo"]);
+    let Some(Command::Fmt {
+        file_names,
+        check,
+        width,
+    }) = args.unwrap().command
+    else {
+        panic!("expected the fmt subcommand");
+    };
+    assert_eq!(file_names, ["a.ceceo"]);
+    assert!(check);
+    assert_eq!(width, 40);
+
+    let args = Arguments::try_parse_from(["ceceo", "fmt", "a.ceceo"]).unwrap();
+    assert!(matches!(
+        args.command,
+        Some(Command::Fmt {
+            check: false,
+            width: DEFAULT_WIDTH,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn fmt_formats_the_code_samples() {
+    use parser::parse_ceceo;
+    use std::path::Path;
+
+    let samples = Path::new(env!("CARGO_MANIFEST_DIR")).join("../code-samples");
+    let scratch = std::env::temp_dir().join(format!("ceceo-fmt-{}", process::id()));
+    fs::create_dir_all(&scratch).unwrap();
+    for entry in fs::read_dir(samples).unwrap() {
+        let path = entry.unwrap().path();
+        let original = fs::read_to_string(&path).unwrap();
+        for width in [DEFAULT_WIDTH, 20] {
+            let copy = scratch.join(path.file_name().unwrap());
+            fs::write(&copy, &original).unwrap();
+            let file_names = [copy.to_string_lossy().into_owned()];
+
+            // Checking leaves the file alone
+            let was_formatted = format_files(&file_names, true, width);
+            assert_eq!(fs::read_to_string(&copy).unwrap(), original);
+
+            assert!(format_files(&file_names, false, width), "{path:?}");
+            let formatted = fs::read_to_string(&copy).unwrap();
+            assert_eq!(was_formatted, formatted == original);
+            assert_eq!(parse_ceceo(&formatted), parse_ceceo(&original), "{path:?}");
+            assert!(format_files(&file_names, true, width), "{path:?}");
         }
     }
+    fs::remove_dir_all(scratch).unwrap();
 }
@@ -0,0 +1,267 @@
+use std::{fmt::Debug, sync::Arc};
+
+use parser::{ast::Atom, number::Number};
+
+use crate::{
+    eval_result::EvalResult,
+    procs_impl::VOID,
+    runtime_error::{Arity, RuntimeError},
+};
+
+type NativeFnBox = dyn Fn(Vec<EvalResult>) -> Result<EvalResult, RuntimeError> + Send + Sync;
+
+/// A procedure implemented in Rust by the host application. It's a value
+/// like the procedures defined in a program: it's bound to a name in an
+/// environment, and it can be passed around and stored.
+///
+/// It's called with its arguments already evaluated, after checking that
+/// there are as many as its arity allows.
+#[derive(Clone)]
+pub struct NativeProc {
+    name: String,
+    arity: Arity,
+    func: Arc<NativeFnBox>,
+}
+
+impl NativeProc {
+    /// A procedure taking the values of its arguments as they are, for the
+    /// ones that need any number of arguments or of any type.
+    pub fn new(
+        name: impl Into<String>,
+        arity: Arity,
+        func: impl Fn(Vec<EvalResult>) -> Result<EvalResult, RuntimeError> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            arity,
+            func: Arc::new(func),
+        }
+    }
+
+    /// A procedure calling a closure with typed parameters, like
+    /// `|x: i64, y: i64| x + y`. Its arity is the number of parameters, and
+    /// each argument is converted to the type of its parameter.
+    pub fn from_fn<Args>(name: impl Into<String>, func: impl NativeFn<Args>) -> Self {
+        let arity = func.arity();
+        Self::new(name, arity, move |args| func.call(args))
+    }
+
+    #[must_use]
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    #[must_use]
+    pub const fn get_arity(&self) -> Arity {
+        self.arity
+    }
+
+    pub(crate) fn call(&self, args: Vec<EvalResult>) -> Result<EvalResult, RuntimeError> {
+        self.arity.check(&args)?;
+        (self.func)(args)
+    }
+}
+
+impl PartialEq for NativeProc {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.func, &other.func)
+    }
+}
+
+impl Debug for NativeProc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NativeProc")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A Rust closure that can be called as a procedure, see
+/// [`NativeProc::from_fn`]. `Args` is the tuple of its parameter types.
+pub trait NativeFn<Args>: Send + Sync + 'static {
+    fn arity(&self) -> Arity;
+    fn call(&self, args: Vec<EvalResult>) -> Result<EvalResult, RuntimeError>;
+}
+
+macro_rules! count {
+    () => (0);
+    ($head:ident $($tail:ident)*) => (1 + count!($($tail)*));
+}
+
+macro_rules! impl_native_fn {
+    ($($arg:ident),*) => {
+        impl<F, R, $($arg),*> NativeFn<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R + Send + Sync + 'static,
+            R: IntoValue,
+            $($arg: FromValue,)*
+        {
+            fn arity(&self) -> Arity {
+                Arity::Exactly(count!($($arg)*))
+            }
+
+            #[allow(non_snake_case, unused_mut, unused_variables)]
+            fn call(&self, args: Vec<EvalResult>) -> Result<EvalResult, RuntimeError> {
+                let mut args = args.into_iter();
+                $(let $arg = $arg::from_value(args.next().unwrap())?;)*
+                self($($arg),*).into_value()
+            }
+        }
+    };
+}
+
+impl_native_fn!();
+impl_native_fn!(A);
+impl_native_fn!(A, B);
+impl_native_fn!(A, B, C);
+impl_native_fn!(A, B, C, D);
+
+/// Converts the value of an argument to the type of a parameter of a
+/// native procedure.
+pub trait FromValue: Sized {
+    fn from_value(value: EvalResult) -> Result<Self, RuntimeError>;
+}
+
+/// Converts what a native procedure returns to a value. A `Result` fails
+/// the call with its error.
+pub trait IntoValue {
+    fn into_value(self) -> Result<EvalResult, RuntimeError>;
+}
+
+impl FromValue for EvalResult {
+    fn from_value(value: EvalResult) -> Result<Self, RuntimeError> {
+        Ok(value)
+    }
+}
+
+impl FromValue for Number {
+    fn from_value(value: EvalResult) -> Result<Self, RuntimeError> {
+        match value {
+            EvalResult::Atom(Atom::Num(num)) => Ok(num),
+            value => Err(RuntimeError::type_error("number", value)),
+        }
+    }
+}
+
+impl FromValue for i64 {
+    fn from_value(value: EvalResult) -> Result<Self, RuntimeError> {
+        let num = Number::from_value(value)?;
+        let Some(int) = num.as_integer() else {
+            return Err(RuntimeError::type_error("exact integer", num));
+        };
+
+        Self::try_from(int).map_err(|_| RuntimeError::out_of_range("Integer", num))
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: EvalResult) -> Result<Self, RuntimeError> {
+        Ok(Number::from_value(value)?.to_f64())
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: EvalResult) -> Result<Self, RuntimeError> {
+        match value {
+            EvalResult::Atom(Atom::Str(string)) => Ok(string),
+            value => Err(RuntimeError::type_error("string", value)),
+        }
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: EvalResult) -> Result<Self, RuntimeError> {
+        match value {
+            EvalResult::Atom(Atom::Bool(bool)) => Ok(bool),
+            value => Err(RuntimeError::type_error("boolean", value)),
+        }
+    }
+}
+
+impl FromValue for char {
+    fn from_value(value: EvalResult) -> Result<Self, RuntimeError> {
+        match value {
+            EvalResult::Atom(Atom::Char(c)) => Ok(c),
+            value => Err(RuntimeError::type_error("char", value)),
+        }
+    }
+}
+
+/// The items of a list, each converted to `T`
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: EvalResult) -> Result<Self, RuntimeError> {
+        match value {
+            EvalResult::QuoteList(list) => list
+                .iter()
+                .map(|datum| T::from_value(EvalResult::from_datum(datum)))
+                .collect(),
+            value => Err(RuntimeError::type_error("list", value)),
+        }
+    }
+}
+
+impl IntoValue for EvalResult {
+    fn into_value(self) -> Result<EvalResult, RuntimeError> {
+        Ok(self)
+    }
+}
+
+impl IntoValue for () {
+    fn into_value(self) -> Result<EvalResult, RuntimeError> {
+        Ok(VOID.clone())
+    }
+}
+
+impl IntoValue for Number {
+    fn into_value(self) -> Result<EvalResult, RuntimeError> {
+        Ok(EvalResult::Atom(Atom::Num(self)))
+    }
+}
+
+impl IntoValue for i64 {
+    fn into_value(self) -> Result<EvalResult, RuntimeError> {
+        Number::from(self).into_value()
+    }
+}
+
+impl IntoValue for f64 {
+    fn into_value(self) -> Result<EvalResult, RuntimeError> {
+        Number::from(self).into_value()
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Result<EvalResult, RuntimeError> {
+        Ok(EvalResult::Atom(Atom::Str(self)))
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> Result<EvalResult, RuntimeError> {
+        Ok(EvalResult::Atom(Atom::Bool(self)))
+    }
+}
+
+impl IntoValue for char {
+    fn into_value(self) -> Result<EvalResult, RuntimeError> {
+        Ok(EvalResult::Atom(Atom::Char(self)))
+    }
+}
+
+/// A list of the items
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn into_value(self) -> Result<EvalResult, RuntimeError> {
+        let list = self
+            .into_iter()
+            .map(|item| item.into_value()?.into_datum())
+            .collect::<Result<_, _>>()?;
+        Ok(EvalResult::QuoteList(list))
+    }
+}
+
+impl<T: IntoValue> IntoValue for Result<T, RuntimeError> {
+    fn into_value(self) -> Result<EvalResult, RuntimeError> {
+        self?.into_value()
+    }
+}
@@ -23,15 +23,9 @@ static NUMERIC_PROCS_MAP: LazyLock<BiHashMap<NumericProcs, &'static str>> = Lazy
 impl<'a> TryFrom<&'a str> for NumericProcs {
     type Error = &'static str;
     fn try_from(c: &'a str) -> Result<Self, Self::Error> {
-        match NUMERIC_PROCS_MAP.get_by_right(c) {
-            Some(gp) => Ok(gp.clone()),
-            None => Err("Unknown operator"),
-        }
-    }
-}
-
-impl<'a> From<NumericProcs> for &'a str {
-    fn from(val: NumericProcs) -> Self {
-        NUMERIC_PROCS_MAP.get_by_left(&val).unwrap()
+        NUMERIC_PROCS_MAP
+            .get_by_right(c)
+            .cloned()
+            .ok_or("Unknown operator")
     }
 }
@@ -1,344 +1,709 @@
 use std::sync::LazyLock;
 
 use crate::{
-    eval_iter::{EvalIter, eval_node}, eval_proc::EvalProc,
-    generic_procs::GenericProcs, numeric_procs::NumericProcs, string_procs::StringProcs, eval_result::EvalResult, expr_interpreter::DEFINITIONS_MAP, user_proc::UserProc,
+    char_procs::CharProcs,
+    environment::Environment,
+    eval_iter::{eval_node, EvalIter},
+    eval_proc::{iter_numbers, EvalProc},
+    eval_result::{EvalResult, SharedVec},
+    generic_procs::GenericProcs,
+    numeric_procs::NumericProcs,
+    runtime_error::{Arity, RuntimeError},
+    string_procs::StringProcs,
+    user_proc::UserProc,
+    vector_procs::VectorProcs,
+};
+use parser::{
+    ast::{Atom, Node},
+    number::Number,
 };
-use parser::ast::{Atom, Node};
 
 pub trait ProcImpls<T, U> {
-    fn perform_proc(&self, proc_type: U) -> T;
+    fn perform_proc(&self, proc_type: U, env: &Environment) -> Result<T, RuntimeError>;
 }
 
-const INCORRECT_ARG_NUM: &str = "Incorrect number of arguments";
-
-impl ProcImpls<i32, NumericProcs> for &[Node] {
-    fn perform_proc(&self, proc_type: NumericProcs) -> i32 {
-        fn sum(node_slice: &[Node]) -> i32 {
+// Exact operands give an exact result, any float operand makes it a float.
+// Exact integers never overflow, they grow as needed.
+impl ProcImpls<Number, NumericProcs> for &[Node] {
+    fn perform_proc(
+        &self,
+        proc_type: NumericProcs,
+        env: &Environment,
+    ) -> Result<Number, RuntimeError> {
+        fn sum(node_slice: &[Node], env: &Environment) -> Result<Number, RuntimeError> {
             if node_slice.is_empty() {
-                return 0;
+                return Ok(Number::from(0));
             }
 
-            EvalProc::<i32>::eval_proc(&node_slice, |acc, e| acc + e)
+            EvalProc::<Number>::eval_proc(&node_slice, env, |acc, e| acc + e)
         }
 
-        fn mult(node_slice: &[Node]) -> i32 {
+        fn mult(node_slice: &[Node], env: &Environment) -> Result<Number, RuntimeError> {
             if node_slice.is_empty() {
-                return 1;
+                return Ok(Number::from(1));
             }
 
-            EvalProc::<i32>::eval_proc(&node_slice, |acc, e| acc * e)
+            EvalProc::<Number>::eval_proc(&node_slice, env, |acc, e| acc * e)
         }
 
-        fn subtract(node_slice: &[Node]) -> i32 {
-            let ret = EvalProc::<i32>::eval_proc(&node_slice, |acc, e| acc - e);
+        fn subtract(node_slice: &[Node], env: &Environment) -> Result<Number, RuntimeError> {
+            Arity::AtLeast(1).check(node_slice)?;
+            let ret = EvalProc::<Number>::eval_proc(&node_slice, env, |acc, e| acc - e)?;
             if node_slice.len() == 1 {
-                return -ret;
+                return Ok(-ret);
             }
 
-            return ret;
+            return Ok(ret);
         }
 
-        fn div(node_slice: &[Node]) -> i32 {
-            let ret = EvalProc::<i32>::eval_proc(&node_slice, |acc, e| acc / e);
-            if node_slice.len() == 1 {
-                return 1 / ret;
+        fn div(node_slice: &[Node], env: &Environment) -> Result<Number, RuntimeError> {
+            Arity::AtLeast(1).check(node_slice)?;
+            let nums = iter_numbers(node_slice, env).collect::<Result<Vec<_>, _>>()?;
+            let (first, rest) = nums.split_first().unwrap();
+            // `(/ x)` is `1/x`
+            if rest.is_empty() {
+                return Ok(Number::from(1).checked_div(first.clone())?);
             }
 
-            return ret;
+            return Ok(rest
+                .iter()
+                .cloned()
+                .try_fold(first.clone(), Number::checked_div)?);
         }
-        
-        fn modulo(node_slice: &[Node]) -> i32 {
-            if node_slice.len() != 2 {
-                panic!("{INCORRECT_ARG_NUM}");
-            }
 
-            let ret = EvalProc::<i32>::eval_proc(&node_slice, |acc, e| acc % e);
-            return ret;
+        fn modulo(node_slice: &[Node], env: &Environment) -> Result<Number, RuntimeError> {
+            Arity::Exactly(2).check(node_slice)?;
+            let mut nums = iter_numbers(node_slice, env);
+            let (dividend, divisor) = (nums.next().unwrap()?, nums.next().unwrap()?);
+            return Ok(dividend.modulo(divisor)?);
         }
 
         match proc_type {
-            NumericProcs::Sum => sum(self),
-            NumericProcs::Subtract => subtract(self),
-            NumericProcs::Mult => mult(self),
-            NumericProcs::Div => div(self),
-            NumericProcs::Modulo => modulo(self)
+            NumericProcs::Sum => sum(self, env),
+            NumericProcs::Subtract => subtract(self, env),
+            NumericProcs::Mult => mult(self, env),
+            NumericProcs::Div => div(self, env),
+            NumericProcs::Modulo => modulo(self, env),
         }
     }
 }
 
 impl ProcImpls<String, StringProcs> for &[Node] {
-    fn perform_proc(&self, bop_type: StringProcs) -> String {
-        fn append_strings(node_slice: &[Node]) -> String {
-            if node_slice.len() == 1 {
-                return "".to_string();
-            }
-            
-            EvalProc::<String>::eval_proc(&node_slice, |acc, e| acc + &e)
+    fn perform_proc(
+        &self,
+        bop_type: StringProcs,
+        env: &Environment,
+    ) -> Result<String, RuntimeError> {
+        fn append_strings(node_slice: &[Node], env: &Environment) -> Result<String, RuntimeError> {
+            EvalProc::<String>::eval_proc(&node_slice, env, |acc, e| acc + &e)
         }
 
         match bop_type {
-            StringProcs::Append => append_strings(self),
+            StringProcs::Append => append_strings(self, env),
         }
     }
 }
 
-static VOID: LazyLock<EvalResult> = LazyLock::new(|| EvalResult::QuoteAtom(Atom::Symbol("<void>".to_string())));
+pub static VOID: LazyLock<EvalResult> =
+    LazyLock::new(|| EvalResult::QuoteAtom(Atom::Symbol("<void>".to_string())));
 
-fn node_is_false(node: &Node, should_eval: bool) -> bool {
-    if should_eval {
-        return eval_result_is_false(&eval_node(node));
-    }
+fn node_is_false(node: &Node, env: &Environment) -> Result<bool, RuntimeError> {
+    return Ok(eval_result_is_false(&eval_node(node, env)?));
+}
 
-    if let Node::Atom(a) = node 
-    && let Atom::Bool(b) = a 
-    && b == &false {
+fn eval_result_is_false(er: &EvalResult) -> bool {
+    if let EvalResult::Atom(atom) = er
+        && let Atom::Bool(bool) = atom
+        && bool == &false
+    {
         return true;
     }
 
     return false;
 }
 
-fn eval_result_is_false(er: &EvalResult) -> bool {
-    if let EvalResult::Atom(atom) = er 
-    && let Atom::Bool(bool) = atom
-    && bool == &false {
-        return true;
-    } 
+fn eval_num(node: &Node, env: &Environment) -> Result<i32, RuntimeError> {
+    let er = eval_node(node, env)?;
+    if let EvalResult::Atom(atom) = &er
+        && let Atom::Num(num) = atom
+    {
+        let Some(int) = num.as_integer() else {
+            return Err(RuntimeError::type_error("exact integer", num));
+        };
 
-    return false;
+        return i32::try_from(int).map_err(|_| RuntimeError::out_of_range("Integer", num));
+    }
+
+    Err(RuntimeError::type_error("number", er))
 }
 
-pub fn evaluate_and_return_last(node_list: &[Node]) -> Option<EvalResult> {
+pub fn evaluate_and_return_last(
+    node_list: &[Node],
+    env: &Environment,
+) -> Result<Option<EvalResult>, RuntimeError> {
     let mut ret = None;
     for node in node_list {
-        ret = Some(eval_node(node));
+        ret = Some(eval_node(node, env)?);
     }
 
-    return ret;
+    return Ok(ret);
 }
 
-impl ProcImpls<EvalResult, GenericProcs> for &[Node] {
-    fn perform_proc<'b>(&'b self, proc_type: GenericProcs) -> EvalResult {
-        fn and(node_slice: &[Node]) -> EvalResult {
-            const DEFAULT: EvalResult = EvalResult::Atom(Atom::Bool(true));
-            let mut ret = DEFAULT;
-
-            for n in node_slice.iter_eval() {
-                if eval_result_is_false(&n) {
-                    return EvalResult::Atom(Atom::Bool(false));
-                } else {
-                    ret = n;
-                }
-            }
+fn and(node_slice: &[Node], env: &Environment) -> Result<EvalResult, RuntimeError> {
+    const DEFAULT: EvalResult = EvalResult::Atom(Atom::Bool(true));
+    let mut ret = DEFAULT;
 
-            return ret;
+    for n in node_slice.iter_eval(env) {
+        let n = n?;
+        if eval_result_is_false(&n) {
+            return Ok(EvalResult::Atom(Atom::Bool(false)));
         }
+        ret = n;
+    }
 
-        fn or(node_slice: &[Node]) -> EvalResult {
-            const DEFAULT: EvalResult = EvalResult::Atom(Atom::Bool(false));
+    return Ok(ret);
+}
 
-            for n in node_slice.iter_eval() {
-                if eval_result_is_false(&n) {
-                    continue
-                } else {
-                    return n;
-                }
-            }
+fn or(node_slice: &[Node], env: &Environment) -> Result<EvalResult, RuntimeError> {
+    const DEFAULT: EvalResult = EvalResult::Atom(Atom::Bool(false));
 
-            return DEFAULT;
+    for n in node_slice.iter_eval(env) {
+        let n = n?;
+        if !eval_result_is_false(&n) {
+            return Ok(n);
         }
+    }
 
-        fn if_proc(node_slice: &[Node]) -> EvalResult {
-            if node_slice.len() != 3 {
-                panic!("{INCORRECT_ARG_NUM}");
-            }
+    return Ok(DEFAULT);
+}
 
-            let test_expr = &node_slice[0];
-            if node_is_false(test_expr, false) {
-                let else_expr = &node_slice[2];
-                return eval_node(else_expr);
-            } else {
-                let then_expr = &node_slice[1];
-                return eval_node(then_expr);
-            }
+fn if_proc(node_slice: &[Node], env: &Environment) -> Result<EvalResult, RuntimeError> {
+    Arity::Exactly(3).check(node_slice)?;
+
+    let test_expr = &node_slice[0];
+    if node_is_false(test_expr, env)? {
+        let else_expr = &node_slice[2];
+        return eval_node(else_expr, env);
+    }
+
+    let then_expr = &node_slice[1];
+    return eval_node(then_expr, env);
+}
+
+fn display(node_slice: &[Node], env: &Environment) -> Result<EvalResult, RuntimeError> {
+    Arity::Exactly(1).check(node_slice)?;
+
+    let first_eval = eval_node(&node_slice[0], env)?;
+    println!("{first_eval}");
+
+    return Ok(VOID.clone());
+}
+
+fn not(node_slice: &[Node], env: &Environment) -> Result<EvalResult, RuntimeError> {
+    Arity::Exactly(1).check(node_slice)?;
+
+    let first = &node_slice[0];
+    if node_is_false(first, env)? {
+        return Ok(EvalResult::Atom(Atom::Bool(true)));
+    }
+
+    return Ok(EvalResult::Atom(Atom::Bool(false)));
+}
+
+fn cond(node_slice: &[Node], env: &Environment) -> Result<EvalResult, RuntimeError> {
+    fn node_is_else(node: &Node) -> bool {
+        if let Node::Atom(atom) = node
+            && let Atom::Symbol(sym) = atom
+            && sym == &"else".to_string()
+        {
+            return true;
         }
 
-        fn display(node_slice: &[Node]) -> EvalResult {
-            if node_slice.len() != 1 {
-                panic!("{INCORRECT_ARG_NUM}");
+        return false;
+    }
+
+    fn evaluate_conds(
+        node_lists: &[&[Node]],
+        env: &Environment,
+    ) -> Result<EvalResult, RuntimeError> {
+        let Some(list) = node_lists.first() else {
+            return Ok(VOID.clone());
+        };
+
+        let test_expr = &list[0];
+        if node_is_else(test_expr) {
+            if node_lists.len() != 1 {
+                return Err(RuntimeError::bad_syntax("Else clause must be last"));
             }
 
-            let first_eval = eval_node(&node_slice[0]);
-            println!("{first_eval}");
+            return evaluate_and_return_last(&list[1..], env)?
+                .ok_or_else(|| RuntimeError::bad_syntax("Missing expressions in `else' clause"));
+        }
 
-            return VOID.clone();
+        if node_is_false(test_expr, env)? {
+            return evaluate_conds(&node_lists[1..], env);
         }
 
-        fn not(node_slice: &[Node]) -> EvalResult {
-            if node_slice.len() != 1 {
-                panic!("{INCORRECT_ARG_NUM}");
-            }
+        return Ok(evaluate_and_return_last(list, env)?.unwrap_or_else(|| VOID.clone()));
+    }
 
-            let first = &node_slice[0];
-            if node_is_false(first, false) {
-                return EvalResult::Atom(Atom::Bool(true));
-            }
+    let node_lists: Vec<&[Node]> = node_slice
+        .iter()
+        .map(|n| match n {
+            Node::List(l) if !l.is_empty() => Ok(l.as_slice()),
+            _ => Err(RuntimeError::bad_syntax("Bad test clause for cond")),
+        })
+        .collect::<Result<_, _>>()?;
+
+    return evaluate_conds(&node_lists, env);
+}
+
+fn test_number(
+    node_slice: &[Node],
+    env: &Environment,
+    test_expr: impl Fn(&Number) -> bool,
+) -> Result<EvalResult, RuntimeError> {
+    Arity::Exactly(1).check(node_slice)?;
+
+    let first = eval_node(&node_slice[0], env)?;
+    if let EvalResult::Atom(atom) = &first
+        && let Atom::Num(num) = atom
+    {
+        return Ok(EvalResult::Atom(Atom::Bool(test_expr(num))));
+    }
+
+    Err(RuntimeError::type_error("number", first))
+}
+
+fn is_positive(node_slice: &[Node], env: &Environment) -> Result<EvalResult, RuntimeError> {
+    test_number(node_slice, env, Number::is_positive)
+}
+
+fn is_zero(node_slice: &[Node], env: &Environment) -> Result<EvalResult, RuntimeError> {
+    test_number(node_slice, env, Number::is_zero)
+}
+
+fn define(node_slice: &[Node], env: &Environment) -> Result<EvalResult, RuntimeError> {
+    Arity::Exactly(2).check(node_slice)?;
+
+    let first = &node_slice[0];
+    let second = &node_slice[1];
+    if let Node::Atom(atom) = first
+        && let Atom::Symbol(sym) = atom
+    {
+        let value = eval_node(second, env)?;
+        env.define(sym, value);
+        return Ok(VOID.clone());
+    }
 
-            return EvalResult::Atom(Atom::Bool(false));
+    Err(RuntimeError::bad_syntax(
+        "The first argument for define must be a symbol",
+    ))
+}
+
+fn lambda(node_slice: &[Node], env: &Environment) -> Result<EvalResult, RuntimeError> {
+    fn arg_name(node: &Node) -> Result<String, RuntimeError> {
+        if let Node::Atom(atom) = node
+            && let Atom::Symbol(sym) = atom
+        {
+            return Ok(sym.to_owned());
         }
 
-        fn cond(node_slice: &[Node]) -> EvalResult {
-            fn node_is_else(node: &Node) -> bool {
-                if let Node::Atom(atom) = node 
-                && let Atom::Symbol(sym) = atom
-                && sym == &"else".to_string() {
-                    return true;
-                }
+        Err(RuntimeError::bad_syntax(
+            "Incorrect lambda syntax: Argument must be a symbol",
+        ))
+    }
 
-                return false;
-            }
+    Arity::AtLeast(2).check(node_slice)?;
 
-            let node_lists: Vec<&[Node]> = node_slice.iter().map(|n| {
-                match n {
-                    Node::List(l) if !l.is_empty() => l.as_slice(),
-                    _ => panic!("Bad test clause for cond")
-                }
-            }).collect();
-
-            fn evaluate_conds(node_lists: &[&[Node]]) -> EvalResult {
-                let length = node_lists.len();
-                for list in node_lists {
-                    let test_expr = &list[0];
-                    if node_is_else(test_expr) {
-                        return match length {
-                            1 => evaluate_and_return_last(&list[1..]).expect("Missing expressions in `else' clause"),
-                            _ => panic!("Else clause must be last")
-                        }
-                    }
-
-                    if node_is_false(test_expr, true) {
-                        return evaluate_conds(&node_lists[1..]);
-                    }
-
-                    return evaluate_and_return_last(list).unwrap_or(VOID.clone());
-                }
+    let arg_decl = &node_slice[0];
+    let body = &node_slice[1..];
 
-                return VOID.clone();
-            }
+    if let Node::Atom(atom) = arg_decl {
+        if let Atom::Symbol(sym) = atom {
+            return Ok(EvalResult::Proc(
+                UserProc::new(vec![sym.to_owned()], body.to_owned(), env.clone())?
+                    .quote_starts_at(0),
+            ));
+        }
+
+        return Err(RuntimeError::bad_syntax(
+            "Incorrect lambda syntax: Bad arguments",
+        ));
+    }
+
+    if let Node::List(list) = arg_decl {
+        let arg_vec = list.iter().map(arg_name).collect::<Result<_, _>>()?;
+        return Ok(EvalResult::Proc(
+            UserProc::new(arg_vec, body.to_owned(), env.clone())?.quote_starts_at(0),
+        ));
+    }
+
+    // (lambda (a b . rest) ...) takes any number of arguments after `b`
+    if let Node::DottedList(list, rest) = arg_decl {
+        let arg_vec = list.iter().map(arg_name).collect::<Result<_, _>>()?;
+        let proc =
+            UserProc::new(arg_vec, body.to_owned(), env.clone())?.with_rest_arg(arg_name(rest)?)?;
+        return Ok(EvalResult::Proc(proc.quote_starts_at(0)));
+    }
+
+    Err(RuntimeError::bad_syntax(
+        "Incorrect lambda syntax: Bad arguments",
+    ))
+}
+
+fn num_eq(node_slice: &[Node], env: &Environment) -> Result<EvalResult, RuntimeError> {
+    Arity::AtLeast(1).check(node_slice)?;
 
-            return evaluate_conds(&node_lists);
+    let nums = iter_numbers(node_slice, env).collect::<Result<Vec<_>, _>>()?;
+    let first = &nums[0];
+    let is_equal = |item: &Number| item.numeric_cmp(first) == Some(std::cmp::Ordering::Equal);
+    return Ok(EvalResult::Atom(Atom::Bool(nums[1..].iter().all(is_equal))));
+}
+
+impl ProcImpls<EvalResult, GenericProcs> for &[Node] {
+    fn perform_proc(
+        &self,
+        proc_type: GenericProcs,
+        env: &Environment,
+    ) -> Result<EvalResult, RuntimeError> {
+        match proc_type {
+            GenericProcs::And => and(self, env),
+            GenericProcs::Or => or(self, env),
+            GenericProcs::If => if_proc(self, env),
+            GenericProcs::Display => display(self, env),
+            GenericProcs::Not => not(self, env),
+            GenericProcs::Cond => cond(self, env),
+            GenericProcs::IsPositive => is_positive(self, env),
+            GenericProcs::IsZero => is_zero(self, env),
+            GenericProcs::Define => define(self, env),
+            GenericProcs::Lambda => lambda(self, env),
+            GenericProcs::NumEq => num_eq(self, env),
         }
+    }
+}
 
-        fn test_number(node_slice: &[Node], test_expr: impl Fn(&i32) -> bool) -> EvalResult {
-            if node_slice.len() != 1 {
-                panic!("{INCORRECT_ARG_NUM}");
+impl ProcImpls<EvalResult, CharProcs> for &[Node] {
+    fn perform_proc(
+        &self,
+        proc_type: CharProcs,
+        env: &Environment,
+    ) -> Result<EvalResult, RuntimeError> {
+        fn eval_char(node: &Node, env: &Environment) -> Result<char, RuntimeError> {
+            match eval_node(node, env)? {
+                EvalResult::Atom(Atom::Char(c)) => Ok(c),
+                er => Err(RuntimeError::type_error("char", er)),
             }
+        }
 
-            let first = eval_node(&node_slice[0]);
-            if let EvalResult::Atom(atom) = first
-            && let Atom::Num(num) = atom {
-                return EvalResult::Atom(Atom::Bool(test_expr(&num)));
-            }
+        fn single_char(node_slice: &[Node], env: &Environment) -> Result<char, RuntimeError> {
+            Arity::Exactly(1).check(node_slice)?;
 
-            panic!("Expected number");
+            eval_char(&node_slice[0], env)
         }
 
-        fn is_positive(node_slice: &[Node]) -> EvalResult {
-            test_number(node_slice, |num| num > &0)
-        }
-        
-        fn is_zero(node_slice: &[Node]) -> EvalResult {
-            test_number(node_slice, |num| num == &0)
+        fn map_char(
+            node_slice: &[Node],
+            env: &Environment,
+            f: impl Fn(char) -> char,
+        ) -> Result<EvalResult, RuntimeError> {
+            return Ok(EvalResult::Atom(Atom::Char(f(single_char(
+                node_slice, env,
+            )?))));
         }
 
-        fn define(node_slice: &[Node]) -> EvalResult {
-            if node_slice.len() != 2 {
-                panic!("{INCORRECT_ARG_NUM}");
-            }
+        fn test_char(
+            node_slice: &[Node],
+            env: &Environment,
+            test_expr: impl Fn(char) -> bool,
+        ) -> Result<EvalResult, RuntimeError> {
+            return Ok(EvalResult::Atom(Atom::Bool(test_expr(single_char(
+                node_slice, env,
+            )?))));
+        }
 
-            let first = &node_slice[0];
-            let second = &node_slice[1];
-            if let Node::Atom(atom) = first &&
-            let Atom::Symbol(sym) = atom {
-                let mut def_map = DEFINITIONS_MAP.write().unwrap();
-                def_map.insert(sym.to_owned(), second.clone());
-                return VOID.clone();
+        // Only the case mappings that are a single char are applied
+        fn single_char_mapping(c: char, mut mapping: impl ExactSizeIterator<Item = char>) -> char {
+            if mapping.len() == 1 {
+                return mapping.next().unwrap();
             }
 
-            panic!("The first argument for define must be a symbol");
+            return c;
         }
-        
-        fn lambda(node_slice: &[Node]) -> EvalResult {
-            if node_slice.len() < 2 {
-                panic!("{INCORRECT_ARG_NUM}");
-            }
 
-            let arg_decl = &node_slice[0];
-            let body = &node_slice[1..];
+        fn is_char(node_slice: &[Node], env: &Environment) -> Result<EvalResult, RuntimeError> {
+            Arity::Exactly(1).check(node_slice)?;
 
-            if body.is_empty() {
-                panic!("Incorrect lambda syntax: Missing body");
-            }
+            let is_char = matches!(
+                eval_node(&node_slice[0], env)?,
+                EvalResult::Atom(Atom::Char(_))
+            );
+            return Ok(EvalResult::Atom(Atom::Bool(is_char)));
+        }
 
-            if let Node::Atom(atom) = arg_decl {
-                if let Atom::Symbol(sym) = atom {
-                    return EvalResult::Proc(UserProc::new(vec![sym.to_owned()], body.to_owned()).quote_starts_at(0));
-                }
+        fn char_to_integer(
+            node_slice: &[Node],
+            env: &Environment,
+        ) -> Result<EvalResult, RuntimeError> {
+            let code = u32::from(single_char(node_slice, env)?);
+            return Ok(EvalResult::Atom(Atom::Num(code.into())));
+        }
 
-                panic!("Incorrect lambda syntax: Bad arguments");
-            }
+        fn integer_to_char(
+            node_slice: &[Node],
+            env: &Environment,
+        ) -> Result<EvalResult, RuntimeError> {
+            Arity::Exactly(1).check(node_slice)?;
+
+            let code = eval_num(&node_slice[0], env)?;
+            u32::try_from(code)
+                .ok()
+                .and_then(char::from_u32)
+                .map(|c| EvalResult::Atom(Atom::Char(c)))
+                .ok_or_else(|| RuntimeError::out_of_range("Char code", code))
+        }
 
-            if let Node::List(list) = arg_decl {
-                let mut arg_vec = Vec::new();
-                for node in list {
-                    if let Node::Atom(atom) = node
-                    && let Atom::Symbol(sym) = atom {
-                        arg_vec.push(sym.to_owned());
-                    } else {
-                        panic!("Incorrect lambda syntax: Argument must be a symbol");
-                    }
-                }
+        fn char_eq(node_slice: &[Node], env: &Environment) -> Result<EvalResult, RuntimeError> {
+            Arity::AtLeast(1).check(node_slice)?;
 
-                return EvalResult::Proc(UserProc::new(arg_vec, body.to_owned()).quote_starts_at(0));
+            let first = eval_char(&node_slice[0], env)?;
+            for node in &node_slice[1..] {
+                if eval_char(node, env)? != first {
+                    return Ok(EvalResult::Atom(Atom::Bool(false)));
+                }
             }
 
-            panic!("Incorrect lambda syntax: Bad arguments");
+            return Ok(EvalResult::Atom(Atom::Bool(true)));
         }
 
-        fn num_eq(node_slice: &[Node]) -> EvalResult {
-            if node_slice.is_empty() {
-                panic!("{INCORRECT_ARG_NUM}");
-            }
-            if node_slice.len() == 1 {
-                return EvalResult::Atom(Atom::Bool(true));
+        fn string_ref(node_slice: &[Node], env: &Environment) -> Result<EvalResult, RuntimeError> {
+            Arity::Exactly(2).check(node_slice)?;
+
+            let string = match eval_node(&node_slice[0], env)? {
+                EvalResult::Atom(Atom::Str(string)) => string,
+                er => return Err(RuntimeError::type_error("string", er)),
+            };
+            let idx = eval_num(&node_slice[1], env)?;
+            usize::try_from(idx)
+                .ok()
+                .and_then(|idx| string.chars().nth(idx))
+                .map(|c| EvalResult::Atom(Atom::Char(c)))
+                .ok_or_else(|| RuntimeError::out_of_range("Index", idx))
+        }
+
+        match proc_type {
+            CharProcs::IsChar => is_char(self, env),
+            CharProcs::CharToInteger => char_to_integer(self, env),
+            CharProcs::IntegerToChar => integer_to_char(self, env),
+            CharProcs::Upcase => map_char(self, env, |c| single_char_mapping(c, c.to_uppercase())),
+            CharProcs::Downcase => {
+                map_char(self, env, |c| single_char_mapping(c, c.to_lowercase()))
             }
+            CharProcs::IsAlphabetic => test_char(self, env, char::is_alphabetic),
+            CharProcs::IsNumeric => test_char(self, env, char::is_numeric),
+            CharProcs::IsWhitespace => test_char(self, env, char::is_whitespace),
+            CharProcs::CharEq => char_eq(self, env),
+            CharProcs::StringRef => string_ref(self, env),
+        }
+    }
+}
 
-            let mut num_iter = node_slice.iter_eval().map(|er| {
-                if let EvalResult::Atom(atom) = er 
-                && let Atom::Num(num) = atom {
-                    return num;
-                }
+fn eval_vector(node: &Node, env: &Environment) -> Result<SharedVec<EvalResult>, RuntimeError> {
+    match eval_node(node, env)? {
+        EvalResult::Vector(items) => Ok(items),
+        er => Err(RuntimeError::type_error("vector", er)),
+    }
+}
 
-                panic!("The equality operator (=) only work with numbers")
-            });
-            
-            let first = num_iter.nth(0).unwrap();
-            return EvalResult::Atom(Atom::Bool(num_iter.all(|item| item == first)));
+fn eval_bytevector(node: &Node, env: &Environment) -> Result<SharedVec<u8>, RuntimeError> {
+    match eval_node(node, env)? {
+        EvalResult::Bytevector(bytes) => Ok(bytes),
+        er => Err(RuntimeError::type_error("bytevector", er)),
+    }
+}
+
+fn eval_byte(node: &Node, env: &Environment) -> Result<u8, RuntimeError> {
+    let num = eval_num(node, env)?;
+    u8::try_from(num).map_err(|_| RuntimeError::type_error("byte", num))
+}
+
+/// The length of a new vector, which is capped so that asking for a
+/// huge one is an error instead of an allocation failure that aborts
+fn eval_length(node: &Node, env: &Environment) -> Result<usize, RuntimeError> {
+    const MAX_LENGTH: usize = 1 << 24;
+    let num = eval_num(node, env)?;
+    match usize::try_from(num) {
+        Ok(len) if len <= MAX_LENGTH => Ok(len),
+        _ => Err(RuntimeError::out_of_range("Length", num)),
+    }
+}
+
+/// Checks an index evaluated before locking the vector, as the
+/// index expression may use the vector too
+fn check_index(num: i32, len: usize) -> Result<usize, RuntimeError> {
+    match usize::try_from(num) {
+        Ok(idx) if idx < len => Ok(idx),
+        _ => Err(RuntimeError::out_of_range("Index", num)),
+    }
+}
+
+fn length_result(len: usize) -> EvalResult {
+    return EvalResult::Atom(Atom::Num(len.into()));
+}
+
+fn is_type(
+    node_slice: &[Node],
+    env: &Environment,
+    test: impl Fn(&EvalResult) -> bool,
+) -> Result<EvalResult, RuntimeError> {
+    Arity::Exactly(1).check(node_slice)?;
+    return Ok(EvalResult::Atom(Atom::Bool(test(&eval_node(
+        &node_slice[0],
+        env,
+    )?))));
+}
+
+fn make_vector(node_slice: &[Node], env: &Environment) -> Result<EvalResult, RuntimeError> {
+    Arity::Between(1, 2).check(node_slice)?;
+    let len = eval_length(&node_slice[0], env)?;
+    let fill = match node_slice.get(1) {
+        Some(node) => eval_node(node, env)?,
+        None => EvalResult::Atom(Atom::Num(0.into())),
+    };
+
+    return Ok(EvalResult::Vector(SharedVec::new(vec![fill; len])));
+}
+
+fn vector(node_slice: &[Node], env: &Environment) -> Result<EvalResult, RuntimeError> {
+    let items = node_slice.iter_eval(env).collect::<Result<_, _>>()?;
+    return Ok(EvalResult::Vector(SharedVec::new(items)));
+}
+
+fn vector_length(node_slice: &[Node], env: &Environment) -> Result<EvalResult, RuntimeError> {
+    Arity::Exactly(1).check(node_slice)?;
+    let len = eval_vector(&node_slice[0], env)?.read().len();
+    return Ok(length_result(len));
+}
+
+fn vector_ref(node_slice: &[Node], env: &Environment) -> Result<EvalResult, RuntimeError> {
+    Arity::Exactly(2).check(node_slice)?;
+    let items = eval_vector(&node_slice[0], env)?;
+    let idx = eval_num(&node_slice[1], env)?;
+    let items = items.read();
+    let idx = check_index(idx, items.len())?;
+    return Ok(items[idx].clone());
+}
+
+fn vector_set(node_slice: &[Node], env: &Environment) -> Result<EvalResult, RuntimeError> {
+    Arity::Exactly(3).check(node_slice)?;
+    let items = eval_vector(&node_slice[0], env)?;
+    let idx = eval_num(&node_slice[1], env)?;
+    let len = items.read().len();
+    let idx = check_index(idx, len)?;
+    let value = eval_node(&node_slice[2], env)?;
+    items.write()[idx] = value;
+    return Ok(VOID.clone());
+}
+
+fn vector_fill(node_slice: &[Node], env: &Environment) -> Result<EvalResult, RuntimeError> {
+    Arity::Exactly(2).check(node_slice)?;
+    let items = eval_vector(&node_slice[0], env)?;
+    let value = eval_node(&node_slice[1], env)?;
+    items.write().fill(value);
+    return Ok(VOID.clone());
+}
+
+fn vector_to_list(node_slice: &[Node], env: &Environment) -> Result<EvalResult, RuntimeError> {
+    Arity::Exactly(1).check(node_slice)?;
+    let items = eval_vector(&node_slice[0], env)?;
+    let list = items
+        .read()
+        .iter()
+        .cloned()
+        .map(EvalResult::into_datum)
+        .collect::<Result<_, _>>()?;
+    return Ok(EvalResult::QuoteList(list));
+}
+
+fn list_to_vector(node_slice: &[Node], env: &Environment) -> Result<EvalResult, RuntimeError> {
+    Arity::Exactly(1).check(node_slice)?;
+    match eval_node(&node_slice[0], env)? {
+        EvalResult::QuoteList(list) => {
+            let items = list.iter().map(EvalResult::from_datum).collect();
+            Ok(EvalResult::Vector(SharedVec::new(items)))
         }
+        er => Err(RuntimeError::type_error("list", er)),
+    }
+}
+
+fn make_bytevector(node_slice: &[Node], env: &Environment) -> Result<EvalResult, RuntimeError> {
+    Arity::Between(1, 2).check(node_slice)?;
+    let len = eval_length(&node_slice[0], env)?;
+    let fill = match node_slice.get(1) {
+        Some(node) => eval_byte(node, env)?,
+        None => 0,
+    };
+    return Ok(EvalResult::Bytevector(SharedVec::new(vec![fill; len])));
+}
 
+fn bytevector(node_slice: &[Node], env: &Environment) -> Result<EvalResult, RuntimeError> {
+    let bytes = node_slice
+        .iter()
+        .map(|node| eval_byte(node, env))
+        .collect::<Result<_, _>>()?;
+    return Ok(EvalResult::Bytevector(SharedVec::new(bytes)));
+}
+
+fn bytevector_length(node_slice: &[Node], env: &Environment) -> Result<EvalResult, RuntimeError> {
+    Arity::Exactly(1).check(node_slice)?;
+    let len = eval_bytevector(&node_slice[0], env)?.read().len();
+    return Ok(length_result(len));
+}
+
+fn bytevector_u8_ref(node_slice: &[Node], env: &Environment) -> Result<EvalResult, RuntimeError> {
+    Arity::Exactly(2).check(node_slice)?;
+    let bytes = eval_bytevector(&node_slice[0], env)?;
+    let idx = eval_num(&node_slice[1], env)?;
+    let bytes = bytes.read();
+    let idx = check_index(idx, bytes.len())?;
+    return Ok(EvalResult::Atom(Atom::Num(bytes[idx].into())));
+}
+
+fn bytevector_u8_set(node_slice: &[Node], env: &Environment) -> Result<EvalResult, RuntimeError> {
+    Arity::Exactly(3).check(node_slice)?;
+    let bytes = eval_bytevector(&node_slice[0], env)?;
+    let idx = eval_num(&node_slice[1], env)?;
+    let len = bytes.read().len();
+    let idx = check_index(idx, len)?;
+    let byte = eval_byte(&node_slice[2], env)?;
+    bytes.write()[idx] = byte;
+    return Ok(VOID.clone());
+}
+
+impl ProcImpls<EvalResult, VectorProcs> for &[Node] {
+    fn perform_proc(
+        &self,
+        proc_type: VectorProcs,
+        env: &Environment,
+    ) -> Result<EvalResult, RuntimeError> {
         match proc_type {
-            GenericProcs::And => and(self),
-            GenericProcs::Or => or(self),
-            GenericProcs::If => if_proc(self),
-            GenericProcs::Display => display(self),
-            GenericProcs::Not => not(self),
-            GenericProcs::Cond => cond(self),
-            GenericProcs::IsPositive => is_positive(self),
-            GenericProcs::IsZero => is_zero(self),
-            GenericProcs::Define => define(self),
-            GenericProcs::Lambda => lambda(self),
-            GenericProcs::NumEq => num_eq(self),
+            VectorProcs::IsVector => is_type(self, env, |er| matches!(er, EvalResult::Vector(_))),
+            VectorProcs::MakeVector => make_vector(self, env),
+            VectorProcs::Vector => vector(self, env),
+            VectorProcs::VectorLength => vector_length(self, env),
+            VectorProcs::VectorRef => vector_ref(self, env),
+            VectorProcs::VectorSet => vector_set(self, env),
+            VectorProcs::VectorFill => vector_fill(self, env),
+            VectorProcs::VectorToList => vector_to_list(self, env),
+            VectorProcs::ListToVector => list_to_vector(self, env),
+            VectorProcs::IsBytevector => {
+                is_type(self, env, |er| matches!(er, EvalResult::Bytevector(_)))
+            }
+            VectorProcs::MakeBytevector => make_bytevector(self, env),
+            VectorProcs::Bytevector => bytevector(self, env),
+            VectorProcs::BytevectorLength => bytevector_length(self, env),
+            VectorProcs::BytevectorU8Ref => bytevector_u8_ref(self, env),
+            VectorProcs::BytevectorU8Set => bytevector_u8_set(self, env),
         }
     }
 }
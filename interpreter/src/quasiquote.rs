@@ -0,0 +1,88 @@
+use parser::ast::Node;
+
+use crate::{
+    environment::Environment, eval_iter::eval_node, eval_result::EvalResult,
+    runtime_error::RuntimeError,
+};
+
+const BAD_SPLICE: &str = "Invalid unquote-splicing: ,@ must be inside a list";
+
+/// Builds the value of `` `template ``: the template is quoted, except for
+/// the parts marked with `,` (replaced by their value) and `,@` (replaced by
+/// the elements of the list they evaluate to).
+pub fn eval_quasiquote(template: &Node, env: &Environment) -> Result<EvalResult, RuntimeError> {
+    match template {
+        Node::Unquote(node) => eval_node(node, env),
+        Node::UnquoteSplicing(_) => Err(RuntimeError::bad_syntax(BAD_SPLICE)),
+        _ => Ok(quoted_value(fill_template(template, 1, env)?)),
+    }
+}
+
+/// Evaluates the unquotes that belong to the outermost quasiquote. `depth`
+/// counts the quasiquotes around `node`, so unquotes inside a nested
+/// quasiquote are kept as they are.
+fn fill_template(node: &Node, depth: usize, env: &Environment) -> Result<Node, RuntimeError> {
+    let filled = match node {
+        Node::List(list) => Node::List(fill_list(list, depth, env)?),
+        Node::QuoteList(list) => Node::QuoteList(fill_list(list, depth, env)?),
+        Node::DottedList(list, tail) => dotted_list(
+            fill_list(list, depth, env)?,
+            fill_template(tail, depth, env)?,
+        ),
+        Node::QuoteDottedList(list, tail) => Node::QuoteDottedList(
+            fill_list(list, depth, env)?,
+            Box::new(fill_template(tail, depth, env)?),
+        ),
+        Node::Quasiquote(inner) => {
+            Node::Quasiquote(Box::new(fill_template(inner, depth + 1, env)?))
+        }
+        Node::Unquote(inner) if depth == 1 => eval_node(inner, env)?.into_datum()?,
+        Node::Unquote(inner) => Node::Unquote(Box::new(fill_template(inner, depth - 1, env)?)),
+        Node::UnquoteSplicing(_) if depth == 1 => return Err(RuntimeError::bad_syntax(BAD_SPLICE)),
+        Node::UnquoteSplicing(inner) => {
+            Node::UnquoteSplicing(Box::new(fill_template(inner, depth - 1, env)?))
+        }
+        Node::Vector(items) => Node::Vector(fill_list(items, depth, env)?),
+        Node::Atom(_) | Node::QuoteAtom(_) | Node::Bytevector(_) => node.clone(),
+    };
+
+    Ok(filled)
+}
+
+fn fill_list(list: &[Node], depth: usize, env: &Environment) -> Result<Vec<Node>, RuntimeError> {
+    let mut filled = Vec::with_capacity(list.len());
+    for item in list {
+        match item {
+            Node::UnquoteSplicing(inner) if depth == 1 => match eval_node(inner, env)? {
+                EvalResult::QuoteList(items) => filled.extend(items),
+                other => return Err(RuntimeError::type_error("list", other)),
+            },
+            _ => filled.push(fill_template(item, depth, env)?),
+        }
+    }
+
+    Ok(filled)
+}
+
+/// Builds `(list . tail)`, which is a proper list again when an unquoted
+/// tail turned out to be a list.
+fn dotted_list(mut list: Vec<Node>, tail: Node) -> Node {
+    match tail {
+        Node::List(rest) => {
+            list.extend(rest);
+            Node::List(list)
+        }
+        Node::DottedList(rest, tail) => {
+            list.extend(rest);
+            Node::DottedList(list, tail)
+        }
+        tail => Node::DottedList(list, Box::new(tail)),
+    }
+}
+
+fn quoted_value(node: Node) -> EvalResult {
+    match node {
+        Node::Atom(atom) => EvalResult::QuoteAtom(atom),
+        node => EvalResult::from_datum(&node),
+    }
+}
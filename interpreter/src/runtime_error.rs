@@ -0,0 +1,167 @@
+use std::fmt::Display;
+
+use parser::{ast::Node, number::NumericError};
+
+/// An error raised while evaluating a program, along with the innermost
+/// call that raised it, when there's one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeError {
+    pub kind: ErrorKind,
+    pub expr: Option<Node>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// An argument of the wrong type, like the string in `(+ 1 "2")`
+    Type {
+        expected: &'static str,
+        got: String,
+    },
+    /// A call with the wrong number of arguments
+    Arity {
+        expected: Arity,
+        got: usize,
+    },
+    UnboundVariable(String),
+    /// A call to a value that isn't a procedure, like `(1 2)`
+    NotAProcedure(String),
+    /// A value of the right type that the procedure can't take, like the
+    /// index in `(vector-ref #(1 2) 2)`
+    OutOfRange {
+        what: &'static str,
+        value: String,
+    },
+    DivisionByZero,
+    /// A special form or expression that can't be evaluated
+    BadSyntax(String),
+}
+
+/// The number of arguments a procedure takes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+    Exactly(usize),
+    AtLeast(usize),
+    Between(usize, usize),
+}
+
+impl Arity {
+    #[must_use]
+    pub fn accepts(self, count: usize) -> bool {
+        match self {
+            Self::Exactly(n) => count == n,
+            Self::AtLeast(min) => count >= min,
+            Self::Between(min, max) => (min..=max).contains(&count),
+        }
+    }
+
+    /// Fails unless the arity allows `args.len()` arguments
+    pub fn check<T>(self, args: &[T]) -> Result<(), RuntimeError> {
+        if self.accepts(args.len()) {
+            return Ok(());
+        }
+
+        Err(ErrorKind::Arity {
+            expected: self,
+            got: args.len(),
+        }
+        .into())
+    }
+}
+
+impl RuntimeError {
+    #[must_use]
+    pub const fn new(kind: ErrorKind, expr: Node) -> Self {
+        Self {
+            kind,
+            expr: Some(expr),
+        }
+    }
+
+    pub fn type_error(expected: &'static str, got: impl Display) -> Self {
+        ErrorKind::Type {
+            expected,
+            got: got.to_string(),
+        }
+        .into()
+    }
+
+    pub fn out_of_range(what: &'static str, value: impl Display) -> Self {
+        ErrorKind::OutOfRange {
+            what,
+            value: value.to_string(),
+        }
+        .into()
+    }
+
+    pub fn bad_syntax(message: impl Display) -> Self {
+        ErrorKind::BadSyntax(message.to_string()).into()
+    }
+
+    /// Places the error in `expr`, unless it already is in an inner one
+    #[must_use]
+    pub fn in_expr(mut self, expr: impl FnOnce() -> Node) -> Self {
+        if self.expr.is_none() {
+            self.expr = Some(expr());
+        }
+
+        self
+    }
+}
+
+impl From<ErrorKind> for RuntimeError {
+    fn from(kind: ErrorKind) -> Self {
+        Self { kind, expr: None }
+    }
+}
+
+impl From<NumericError> for RuntimeError {
+    fn from(err: NumericError) -> Self {
+        match err {
+            NumericError::DivisionByZero => ErrorKind::DivisionByZero.into(),
+            NumericError::NotAnInteger => ErrorKind::Type {
+                expected: "integer",
+                got: "a non-integer number".to_string(),
+            }
+            .into(),
+        }
+    }
+}
+
+impl Display for Arity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Exactly(n) => write!(f, "{n}"),
+            Self::AtLeast(min) => write!(f, "at least {min}"),
+            Self::Between(min, max) => write!(f, "{min} to {max}"),
+        }
+    }
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Type { expected, got } => {
+                write!(f, "Incorrect type: Expected {expected}, got {got}")
+            }
+            Self::Arity { expected, got } => {
+                write!(f, "Arity mismatch: Expected {expected}, got {got} instead")
+            }
+            Self::UnboundVariable(name) => write!(f, "Unbound variable: {name}"),
+            Self::NotAProcedure(value) => write!(f, "Not a procedure: {value}"),
+            Self::OutOfRange { what, value } => write!(f, "{what} out of range: {value}"),
+            Self::DivisionByZero => write!(f, "Division by zero"),
+            Self::BadSyntax(message) => write!(f, "Bad syntax: {message}"),
+        }
+    }
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.expr {
+            Some(expr) => write!(f, "{} in {expr}", self.kind),
+            None => write!(f, "{}", self.kind),
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {}
@@ -6,7 +6,7 @@ impl<'a> TryFrom<&'a str> for StringProcs {
     type Error = &'static str;
     fn try_from(c: &'a str) -> Result<Self, Self::Error> {
         match c {
-            "string-append" => Ok(StringProcs::Append),
+            "string-append" => Ok(Self::Append),
             _ => Err("Unknown operator"),
         }
     }
@@ -0,0 +1,251 @@
+#![cfg(test)]
+use crate::{
+    eval_result::EvalResult,
+    interpreter::{parse, EvalError, Interpreter},
+    native_proc::NativeProc,
+    runtime_error::{Arity, ErrorKind, RuntimeError},
+};
+use parser::ast::Atom;
+use std::{sync::Arc, thread};
+
+#[test]
+fn interpreters_dont_share_definitions() {
+    let first = Interpreter::new();
+    let second = Interpreter::new();
+    first.eval_str("(define x 1)").unwrap();
+
+    assert_eq!(
+        first.lookup("x"),
+        Some(EvalResult::Atom(Atom::Num(1.into())))
+    );
+    assert_eq!(second.lookup("x"), None);
+    let Err(EvalError::Runtime(err)) = second.eval_str("(+ x 1)") else {
+        panic!("expected a runtime error");
+    };
+    assert_eq!(err.kind, ErrorKind::UnboundVariable("x".to_string()));
+}
+
+#[test]
+fn interpreters_run_in_parallel() {
+    let results: Vec<EvalResult> = thread::scope(|scope| {
+        // Every thread is started before the first one is joined
+        #[allow(clippy::needless_collect)]
+        let handles: Vec<_> = (0..4)
+            .map(|n| {
+                scope.spawn(move || {
+                    let interpreter = Interpreter::new();
+                    interpreter.eval_str(&format!("(define n {n})")).unwrap();
+                    interpreter
+                        .eval_str("(define sum (lambda (n) (if (= n 0) 0 (+ n (sum (- n 1))))))")
+                        .unwrap();
+                    interpreter.eval_str("(sum n)").unwrap().unwrap()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect()
+    });
+
+    let expected: Vec<EvalResult> = [0, 1, 3, 6]
+        .into_iter()
+        .map(|n| EvalResult::Atom(Atom::Num(n.into())))
+        .collect();
+    assert_eq!(results, expected);
+}
+
+#[test]
+fn host_definitions_are_seen_by_programs() {
+    let interpreter = Interpreter::new();
+    interpreter.define("limit", EvalResult::Atom(Atom::Num(10.into())));
+    let result = interpreter.eval_str("(define double (lambda (x) (* 2 x))) (double limit)");
+    assert_eq!(result, Ok(Some(EvalResult::Atom(Atom::Num(20.into())))));
+    assert!(matches!(
+        interpreter.lookup("double"),
+        Some(EvalResult::Proc(_))
+    ));
+
+    assert_eq!(interpreter.eval_str(""), Ok(None));
+    let Err(EvalError::Syntax(diagnostic)) = interpreter.eval_str("(+ 1") else {
+        panic!("expected a syntax error");
+    };
+    assert_eq!(diagnostic.message, "unexpected end of input");
+}
+
+#[test]
+fn parsed_programs_can_be_evaluated_later() {
+    let program = parse("(define x 2) (* x 21)").unwrap();
+    assert_eq!(program.len(), 2);
+
+    let interpreter = Interpreter::new();
+    let results: Vec<EvalResult> = program
+        .iter()
+        .map(|node| interpreter.eval_node(node).unwrap())
+        .collect();
+    assert_eq!(results[1], EvalResult::Atom(Atom::Num(42.into())));
+
+    let diagnostic = parse("(display \"hi)").unwrap_err();
+    assert_eq!(diagnostic.span.start, 9);
+}
+
+#[cfg(test)]
+fn eval_value(interpreter: &Interpreter, source: &str) -> EvalResult {
+    return interpreter.eval_str(source).unwrap().unwrap();
+}
+
+#[cfg(test)]
+fn runtime_error(interpreter: &Interpreter, source: &str) -> RuntimeError {
+    let Err(EvalError::Runtime(err)) = interpreter.eval_str(source) else {
+        panic!("expected a runtime error");
+    };
+    return err;
+}
+
+#[test]
+fn natives_convert_their_arguments() {
+    let interpreter = Interpreter::new();
+    interpreter.register_fn("add", |a: i64, b: i64| a + b);
+    interpreter.register_fn("shout", |s: String| s.to_uppercase());
+    interpreter.register_fn("total", |items: Vec<i64>| items.iter().sum::<i64>());
+
+    assert_eq!(
+        eval_value(&interpreter, "(add 2 (add 1 2))"),
+        EvalResult::Atom(Atom::Num(5.into()))
+    );
+    assert_eq!(
+        eval_value(&interpreter, "(shout \"hi\")"),
+        EvalResult::Atom(Atom::Str("HI".to_string()))
+    );
+    assert_eq!(
+        eval_value(&interpreter, "(total '(1 2 3))"),
+        EvalResult::Atom(Atom::Num(6.into()))
+    );
+
+    let err = runtime_error(&interpreter, "(add 1)");
+    assert_eq!(
+        err.kind,
+        ErrorKind::Arity {
+            expected: Arity::Exactly(2),
+            got: 1
+        }
+    );
+    let err = runtime_error(&interpreter, "(add 1 \"2\")");
+    assert_eq!(
+        err.to_string(),
+        "Incorrect type: Expected number, got 2 in (add 1 \"2\")"
+    );
+    let err = runtime_error(&interpreter, "(add 1 1/2)");
+    assert!(matches!(
+        err.kind,
+        ErrorKind::Type {
+            expected: "exact integer",
+            ..
+        }
+    ));
+}
+
+#[test]
+fn natives_are_values_in_the_environment() {
+    let interpreter = Interpreter::new();
+    interpreter.register_fn("inc", |n: i64| n + 1);
+    interpreter.register(NativeProc::new("count", Arity::AtLeast(0), |args| {
+        Ok(EvalResult::Atom(Atom::Num(args.len().into())))
+    }));
+    interpreter.register_fn("checked-sqrt", |x: f64| {
+        if x < 0.0 {
+            return Err(RuntimeError::out_of_range("Square root argument", x));
+        }
+        Ok(x.sqrt())
+    });
+
+    let program = "(define twice (lambda (f x) (f (f x)))) (twice inc 1)";
+    assert_eq!(
+        eval_value(&interpreter, program),
+        EvalResult::Atom(Atom::Num(3.into()))
+    );
+    assert_eq!(
+        eval_value(&interpreter, "(count 'a \"b\" (inc 1))"),
+        EvalResult::Atom(Atom::Num(3.into()))
+    );
+    assert_eq!(
+        eval_value(&interpreter, "(checked-sqrt 4)"),
+        EvalResult::Atom(Atom::Num(2.0.into()))
+    );
+    let err = runtime_error(&interpreter, "(+ 1 (checked-sqrt -4))");
+    assert_eq!(
+        err.to_string(),
+        "Square root argument out of range: -4 in (checked-sqrt -4)"
+    );
+
+    // Natives are hidden by definitions like any other global
+    interpreter
+        .eval_str("(define inc (lambda (n) (- n 1)))")
+        .unwrap();
+    assert_eq!(
+        eval_value(&interpreter, "(inc 1)"),
+        EvalResult::Atom(Atom::Num(0.into()))
+    );
+    assert!(matches!(
+        interpreter.lookup("count"),
+        Some(EvalResult::Native(_))
+    ));
+    assert_eq!(Interpreter::new().lookup("count"), None);
+}
+
+#[test]
+fn dropped_interpreters_free_their_environment() {
+    let token = Arc::new(());
+    let interpreter = Interpreter::new();
+    let held = token.clone();
+    interpreter.register_fn("probe", move || {
+        i64::try_from(Arc::strong_count(&held)).unwrap()
+    });
+    let program = "
+        (define keep (lambda () (probe)))
+        (define make (lambda (p) (define helper (lambda () (p))) helper))
+        (define from-closure (make probe))
+        (define probe #f)
+        (from-closure)";
+    assert_eq!(
+        eval_value(&interpreter, program),
+        EvalResult::Atom(Atom::Num(2.into()))
+    );
+
+    drop(interpreter);
+    assert_eq!(Arc::strong_count(&token), 1);
+}
+
+#[test]
+fn dropped_interpreters_free_cycles_through_vectors() {
+    let token = Arc::new(());
+    let interpreter = Interpreter::new();
+    let held = token.clone();
+    interpreter.register_fn("probe", move || {
+        i64::try_from(Arc::strong_count(&held)).unwrap()
+    });
+    let program = "
+        (define v (vector probe 0))
+        (vector-set! v 1 v)
+        (define probe #f)";
+    interpreter.eval_str(program).unwrap();
+
+    drop(interpreter);
+    assert_eq!(Arc::strong_count(&token), 1);
+}
+
+#[test]
+fn procs_kept_by_the_host_outlive_their_interpreter() {
+    let interpreter = Interpreter::new();
+    let program = "
+        (define x 5)
+        (define counter (vector 0))
+        (define count (lambda () (vector-set! counter 0 (+ x (vector-ref counter 0))) counter))
+        count";
+    let count = eval_value(&interpreter, program);
+    drop(interpreter);
+
+    let other = Interpreter::new();
+    other.define("count", count);
+    assert_eq!(eval_value(&other, "(count) (count)").to_string(), "#(10)");
+}
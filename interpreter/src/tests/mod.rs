@@ -1 +1,2 @@
+mod interpreter_tests;
 mod proc_tests;
@@ -1,33 +1,54 @@
 #![cfg(test)]
-use crate::{eval_result::EvalResult, expr_interpreter::eval_list};
-use parser::{ast::Atom, parse_ceceo};
+use crate::{
+    environment::Environment,
+    eval_iter::eval_node,
+    eval_result::EvalResult,
+    runtime_error::{Arity, ErrorKind, RuntimeError},
+};
+use parser::{ast::Atom, number::Number, parse_ceceo};
 #[cfg(test)]
-fn get_program_result(program: &str) -> EvalResult {
+fn eval_program(program: &str) -> Result<EvalResult, RuntimeError> {
     let parsed_ceceo = parse_ceceo(program).unwrap();
     let expr = parsed_ceceo.first().unwrap();
-    let result = eval_list(expr);
-    return result;
+    return eval_node(expr, &Environment::new());
+}
+
+#[cfg(test)]
+fn get_program_result(program: &str) -> EvalResult {
+    return eval_program(program).unwrap();
+}
+
+/// Evaluates every form in the same environment, returning the last value
+#[cfg(test)]
+fn get_last_result(program: &str) -> EvalResult {
+    let env = Environment::new();
+    let parsed_ceceo = parse_ceceo(program).unwrap();
+    let mut last = None;
+    for expr in &parsed_ceceo {
+        last = Some(eval_node(expr, &env).unwrap());
+    }
+    return last.unwrap();
 }
 
 #[test]
 fn all_numeric_procs_work() {
     let program = "(+ 10 10 (* 5 2) (/ 40 2) (* 25 2))";
     let result = get_program_result(program);
-    assert_eq!(result, EvalResult::Atom(Atom::Num(100)))
+    assert_eq!(result, EvalResult::Atom(Atom::Num(100.into())));
 }
 
 #[test]
 fn int_sum_returns_zero_if_no_args() {
     let program = "(+)";
     let result = get_program_result(program);
-    assert_eq!(result, EvalResult::Atom(Atom::Num(0)))
+    assert_eq!(result, EvalResult::Atom(Atom::Num(0.into())));
 }
 
 #[test]
 fn int_mult_returns_one_if_no_args() {
     let program = "(*)";
     let result = get_program_result(program);
-    assert_eq!(result, EvalResult::Atom(Atom::Num(1)))
+    assert_eq!(result, EvalResult::Atom(Atom::Num(1.into())));
 }
 
 #[test]
@@ -37,63 +58,63 @@ fn string_append_works() {
     assert_eq!(
         result,
         EvalResult::Atom(Atom::Str("Hello World!".to_string()))
-    )
+    );
 }
 
 #[test]
 fn and_works() {
     let program = "(and #t 5)";
     let result = get_program_result(program);
-    assert_eq!(result, EvalResult::Atom(Atom::Num(5)))
+    assert_eq!(result, EvalResult::Atom(Atom::Num(5.into())));
 }
 
 #[test]
 fn and_returns_true_if_no_args() {
     let program = "(and)";
     let result = get_program_result(program);
-    assert_eq!(result, EvalResult::Atom(Atom::Bool(true)))
+    assert_eq!(result, EvalResult::Atom(Atom::Bool(true)));
 }
 
 #[test]
 fn or_works() {
     let program = "(or #f 5)";
     let result = get_program_result(program);
-    assert_eq!(result, EvalResult::Atom(Atom::Num(5)))
+    assert_eq!(result, EvalResult::Atom(Atom::Num(5.into())));
 }
 
 #[test]
 fn or_returns_true_if_no_args() {
     let program = "(or)";
     let result = get_program_result(program);
-    assert_eq!(result, EvalResult::Atom(Atom::Bool(false)))
+    assert_eq!(result, EvalResult::Atom(Atom::Bool(false)));
 }
 
 #[test]
 fn and_works_with_lists() {
     let program = "(and #t (+ 3 2) (or #t #f) (* 10 10))";
     let result = get_program_result(program);
-    assert_eq!(result, EvalResult::Atom(Atom::Num(100)))
+    assert_eq!(result, EvalResult::Atom(Atom::Num(100.into())));
 }
 
 #[test]
 fn if_then_branch_works_with_true() {
     let program = "(if #t (+ 2 3) (+ 10 5))";
     let result = get_program_result(program);
-    assert_eq!(result, EvalResult::Atom(Atom::Num(5)))
+    assert_eq!(result, EvalResult::Atom(Atom::Num(5.into())));
 }
 
 #[test]
 fn if_then_branch_works_with_any_value() {
     let program = "(if (* 3 (+ 2 5)) (+ 2 3) (+ 10 5))";
     let result = get_program_result(program);
-    assert_eq!(result, EvalResult::Atom(Atom::Num(5)))
+    assert_eq!(result, EvalResult::Atom(Atom::Num(5.into())));
 }
 
 #[test]
 fn if_else_branch_works() {
     let program = "(if #f (+ 2 3) (+ 10 5))";
     let result = get_program_result(program);
-    assert_eq!(result, EvalResult::Atom(Atom::Num(15)))
+    assert_eq!(result, EvalResult::Atom(Atom::Num(15.into())));
 }
 
 // TODO: Test console output
@@ -104,28 +125,28 @@ fn display_works() {
     assert_eq!(
         result,
         EvalResult::QuoteAtom(Atom::Symbol("<void>".to_string()))
-    )
+    );
 }
 
 #[test]
 fn not_works_with_false() {
     let program = "(not #f)";
     let result = get_program_result(program);
-    assert_eq!(result, EvalResult::Atom(Atom::Bool(true)))
+    assert_eq!(result, EvalResult::Atom(Atom::Bool(true)));
 }
 
 #[test]
 fn not_works_with_true() {
     let program = "(not #t)";
     let result = get_program_result(program);
-    assert_eq!(result, EvalResult::Atom(Atom::Bool(false)))
+    assert_eq!(result, EvalResult::Atom(Atom::Bool(false)));
 }
 
 #[test]
 fn not_works_with_any_value() {
     let program = "(not (+ 2 3))";
     let result = get_program_result(program);
-    assert_eq!(result, EvalResult::Atom(Atom::Bool(false)))
+    assert_eq!(result, EvalResult::Atom(Atom::Bool(false)));
 }
 
 #[test]
@@ -135,14 +156,14 @@ fn cond_only() {
     assert_eq!(
         result,
         EvalResult::QuoteAtom(Atom::Symbol("<void>".to_string()))
-    )
+    );
 }
 
 #[test]
 fn cond_only_else() {
     let program = "(cond [else 5])";
     let result = get_program_result(program);
-    assert_eq!(result, EvalResult::Atom(Atom::Num(5)))
+    assert_eq!(result, EvalResult::Atom(Atom::Num(5.into())));
 }
 
 #[test]
@@ -155,33 +176,455 @@ fn cond_full() {
     assert_eq!(
         result,
         EvalResult::QuoteAtom(Atom::Symbol("here".to_string()))
-    )
+    );
 }
 
 #[test]
 fn modulo_works() {
     let program = "(modulo (+ 5 5) (* 1 3))";
     let result = get_program_result(program);
-    assert_eq!(result, EvalResult::Atom(Atom::Num(1)))
+    assert_eq!(result, EvalResult::Atom(Atom::Num(1.into())));
 }
 
 #[test]
 fn is_positive_works() {
     let program = "(positive? 5)";
     let result = get_program_result(program);
-    assert_eq!(result, EvalResult::Atom(Atom::Bool(true)))
+    assert_eq!(result, EvalResult::Atom(Atom::Bool(true)));
 }
 
 #[test]
 fn is_positive_works_false() {
     let program = "(positive? -10)";
     let result = get_program_result(program);
-    assert_eq!(result, EvalResult::Atom(Atom::Bool(false)))
+    assert_eq!(result, EvalResult::Atom(Atom::Bool(false)));
 }
 
 #[test]
 fn is_zero_works_false() {
     let program = "(zero? 100)";
     let result = get_program_result(program);
-    assert_eq!(result, EvalResult::Atom(Atom::Bool(false)))
+    assert_eq!(result, EvalResult::Atom(Atom::Bool(false)));
+}
+
+#[test]
+fn top_level_atoms_evaluate() {
+    assert_eq!(
+        get_program_result("42"),
+        EvalResult::Atom(Atom::Num(42.into()))
+    );
+    assert_eq!(
+        get_program_result("'foo"),
+        EvalResult::QuoteAtom(Atom::Symbol("foo".to_string()))
+    );
+}
+
+#[test]
+fn quasiquote_builds_list() {
+    let program = "`(1 ,(+ 1 1) ,@'(3 4) five)";
+    let result = get_program_result(program);
+    assert_eq!(
+        result,
+        EvalResult::QuoteList(parse_ceceo("1 2 3 4 five").unwrap())
+    );
+}
+
+#[test]
+fn quasiquote_uses_lambda_args() {
+    let program = "((lambda (x ys) `(a ,x ,@ys x)) 1 '(2 3))";
+    let result = get_program_result(program);
+    assert_eq!(
+        result,
+        EvalResult::QuoteList(parse_ceceo("a 1 2 3 x").unwrap())
+    );
+}
+
+#[test]
+fn nested_quasiquote_keeps_inner_unquotes() {
+    let program = "`(a `(b ,(c ,(+ 1 2))))";
+    let result = get_program_result(program);
+    assert_eq!(
+        result,
+        EvalResult::QuoteList(parse_ceceo("a `(b ,(c 3))").unwrap())
+    );
+}
+
+#[test]
+fn quasiquote_atom() {
+    assert_eq!(
+        get_program_result("`foo"),
+        EvalResult::QuoteAtom(Atom::Symbol("foo".to_string()))
+    );
+    assert_eq!(
+        get_program_result("`,(* 2 3)"),
+        EvalResult::Atom(Atom::Num(6.into()))
+    );
+}
+
+#[test]
+fn lambda_rest_argument_gets_extra_args() {
+    let program = "((lambda (a . rest) rest) 1 (+ 1 1) 3)";
+    let result = get_program_result(program);
+    assert_eq!(result, EvalResult::QuoteList(parse_ceceo("2 3").unwrap()));
+}
+
+#[test]
+fn lambda_rest_argument_can_be_empty() {
+    let program = "((lambda (a b . rest) (+ a b)) 1 2)";
+    let result = get_program_result(program);
+    assert_eq!(result, EvalResult::Atom(Atom::Num(3.into())));
+}
+
+#[test]
+fn lambda_arguments_must_be_distinct() {
+    for program in [
+        "(lambda (x x) x)",
+        "(lambda (a . a) a)",
+        "(lambda (a b a . c) a)",
+    ] {
+        let err = eval_program(program).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::BadSyntax(_)), "{program}");
+    }
+}
+
+#[test]
+fn quoted_dotted_pair() {
+    let program = "'((a . 1) (b . 2))";
+    let result = get_program_result(program);
+    assert_eq!(
+        result,
+        EvalResult::QuoteList(parse_ceceo("(a . 1) (b . 2)").unwrap())
+    );
+    assert_eq!(
+        get_program_result("'(a . b)"),
+        EvalResult::QuoteDottedList(
+            parse_ceceo("a").unwrap(),
+            Box::new(parse_ceceo("b").unwrap().remove(0))
+        )
+    );
+}
+
+#[test]
+fn char_procs_work() {
+    assert_eq!(
+        get_program_result("(char->integer #\\A)"),
+        EvalResult::Atom(Atom::Num(65.into()))
+    );
+    assert_eq!(
+        get_program_result("(integer->char 955)"),
+        EvalResult::Atom(Atom::Char('λ'))
+    );
+    assert_eq!(
+        get_program_result("(char-upcase #\\a)"),
+        EvalResult::Atom(Atom::Char('A'))
+    );
+    assert_eq!(
+        get_program_result("(char-downcase #\\x3A3)"),
+        EvalResult::Atom(Atom::Char('σ'))
+    );
+    assert_eq!(
+        get_program_result("(char-whitespace? #\\space)"),
+        EvalResult::Atom(Atom::Bool(true))
+    );
+    assert_eq!(
+        get_program_result("(char=? #\\a #\\a #\\b)"),
+        EvalResult::Atom(Atom::Bool(false))
+    );
+}
+
+#[test]
+fn string_ref_works() {
+    let program = "(string-ref \"aλb\" 1)";
+    let result = get_program_result(program);
+    assert_eq!(result, EvalResult::Atom(Atom::Char('λ')));
+}
+
+#[test]
+fn vector_literal_is_data() {
+    let result = get_program_result("(vector-ref #(1 a \"s\") 1)");
+    assert_eq!(result, EvalResult::QuoteAtom(Atom::Symbol("a".to_string())));
+    let result = get_program_result("(+ (vector-ref #(1 2 3) 2) 1)");
+    assert_eq!(result, EvalResult::Atom(Atom::Num(4.into())));
+}
+
+#[test]
+fn vector_procs_work() {
+    let result = get_program_result("(vector-length (make-vector 3 #t))");
+    assert_eq!(result, EvalResult::Atom(Atom::Num(3.into())));
+    let result = get_program_result("(vector->list (vector 1 (+ 1 1)))");
+    assert_eq!(result, EvalResult::QuoteList(parse_ceceo("1 2").unwrap()));
+    let result = get_program_result("(vector-ref (list->vector '(a b)) 0)");
+    assert_eq!(result, EvalResult::QuoteAtom(Atom::Symbol("a".to_string())));
+}
+
+#[test]
+fn vector_set_is_shared_between_copies() {
+    let vector = get_program_result("(make-vector 2 0)");
+    let EvalResult::Vector(items) = vector.clone() else {
+        panic!("expected a vector");
+    };
+    items.write()[1] = EvalResult::Atom(Atom::Num(5.into()));
+    assert_eq!(vector.to_string(), "#(0 5)");
+}
+
+#[test]
+fn bytevector_procs_work() {
+    let result = get_program_result("(bytevector-u8-ref #u8(1 2 255) 2)");
+    assert_eq!(result, EvalResult::Atom(Atom::Num(255.into())));
+    let result = get_program_result("(bytevector-length (make-bytevector 4 7))");
+    assert_eq!(result, EvalResult::Atom(Atom::Num(4.into())));
+}
+
+#[test]
+fn vector_ref_checks_bounds() {
+    let err = get_runtime_error("(vector-ref #(1 2) 2)");
+    assert_eq!(
+        err.to_string(),
+        "Index out of range: 2 in (vector-ref #(1 2) 2)"
+    );
+}
+
+#[test]
+fn exact_division_gives_rationals() {
+    let result = get_program_result("(/ 1 3)");
+    assert_eq!(result.to_string(), "1/3");
+    let result = get_program_result("(+ 1/3 2/3)");
+    assert_eq!(result, EvalResult::Atom(Atom::Num(1.into())));
+    let result = get_program_result("(/ 5)");
+    assert_eq!(result.to_string(), "1/5");
+}
+
+#[test]
+fn floats_are_contagious() {
+    let result = get_program_result("(+ 1 1/2 0.25)");
+    assert_eq!(result, EvalResult::Atom(Atom::Num(Number::Real(1.75))));
+    let result = get_program_result("(* 2 1e10)");
+    assert_eq!(result, EvalResult::Atom(Atom::Num(Number::Real(2e10))));
+    let result = get_program_result("(= 1 1.0 2/2)");
+    assert_eq!(result, EvalResult::Atom(Atom::Bool(true)));
+}
+
+#[test]
+fn integers_dont_overflow() {
+    let result = get_program_result("(* 4294967296 4294967296 #x10)");
+    assert_eq!(result.to_string(), "295147905179352825856");
+}
+
+#[test]
+fn modulo_has_the_sign_of_the_divisor() {
+    let result = get_program_result("(modulo -7 2)");
+    assert_eq!(result, EvalResult::Atom(Atom::Num(1.into())));
+    let result = get_program_result("(modulo 7.0 -2)");
+    assert_eq!(result, EvalResult::Atom(Atom::Num(Number::Real(-1.0))));
+}
+
+fn get_runtime_error(program: &str) -> RuntimeError {
+    return eval_program(program).unwrap_err();
+}
+
+#[test]
+fn division_by_zero_is_a_runtime_error() {
+    let err = get_runtime_error("(+ 1 (/ 10 (- 2 2)))");
+    assert_eq!(err.kind, ErrorKind::DivisionByZero);
+    assert_eq!(err.expr.unwrap().to_string(), "(/ 10 (- 2 2))");
+
+    let err = get_runtime_error("(/ 0)");
+    assert_eq!(err.expr.unwrap().to_string(), "(/ 0)");
+    let err = get_runtime_error("(modulo 5 0)");
+    assert_eq!(err.kind, ErrorKind::DivisionByZero);
+    let err = get_runtime_error("(modulo 5 1/2)");
+    assert!(matches!(
+        err.kind,
+        ErrorKind::Type {
+            expected: "integer",
+            ..
+        }
+    ));
+}
+
+#[test]
+fn huge_vectors_are_out_of_range() {
+    let err = get_runtime_error("(make-vector 2000000000)");
+    assert!(matches!(
+        err.kind,
+        ErrorKind::OutOfRange { what: "Length", .. }
+    ));
+    let err = get_runtime_error("(make-bytevector 2000000000 1)");
+    assert!(matches!(
+        err.kind,
+        ErrorKind::OutOfRange { what: "Length", .. }
+    ));
+}
+
+#[test]
+fn float_division_by_zero_is_infinite() {
+    let result = get_program_result("(/ 1.0 0)");
+    assert_eq!(result.to_string(), "+inf.0");
+}
+
+#[test]
+fn large_products_promote_to_bignums() {
+    let result = get_program_result("(* 2147483647 2147483647 -1)");
+    assert_eq!(result.to_string(), "-4611686014132420609");
+}
+
+#[test]
+fn closures_capture_their_environment() {
+    let program = "
+        (define make-adder (lambda (n) (lambda (x) (+ x n))))
+        (define add5 (make-adder 5))
+        (add5 10)";
+    assert_eq!(
+        get_last_result(program),
+        EvalResult::Atom(Atom::Num(15.into()))
+    );
+
+    let program = "
+        (define fact (lambda (n) (if (= n 0) 1 (* n (fact (- n 1))))))
+        (fact 10)";
+    assert_eq!(
+        get_last_result(program),
+        EvalResult::Atom(Atom::Num(3_628_800.into()))
+    );
+}
+
+#[test]
+fn arguments_are_evaluated_once() {
+    let program = "
+        (define counter (make-vector 1 0))
+        (define next (lambda () (vector-set! counter 0 (+ (vector-ref counter 0) 1)) (vector-ref counter 0)))
+        ((lambda (x) (+ x x x)) (next))";
+    assert_eq!(
+        get_last_result(program),
+        EvalResult::Atom(Atom::Num(3.into()))
+    );
+}
+
+#[test]
+fn index_may_use_the_vector() {
+    let program = "
+        (define v (vector 1 2 3))
+        (define f (lambda () (vector-set! v 0 9) 0))
+        (vector-ref v (f))";
+    assert_eq!(
+        get_last_result(program),
+        EvalResult::Atom(Atom::Num(9.into()))
+    );
+    let program = "
+        (define v (vector 1 2 3))
+        (vector-set! v (vector-ref v 0) 'a)
+        v";
+    assert_eq!(get_last_result(program).to_string(), "#(1 a 3)");
+    let program = "
+        (define b (bytevector 1 2 3))
+        (define f (lambda () (bytevector-u8-set! b 0 9) 0))
+        (bytevector-u8-ref b (f))";
+    assert_eq!(
+        get_last_result(program),
+        EvalResult::Atom(Atom::Num(9.into()))
+    );
+}
+
+#[test]
+fn vectors_may_hold_themselves() {
+    let program = "
+        (define v (make-vector 1 0))
+        (vector-set! v 0 v)
+        v";
+    let v = get_last_result(program);
+    assert_eq!(v.to_string(), "#0=#(#0#)");
+    let program = "
+        (define v (vector 1 2))
+        (vector-fill! v v)
+        (vector 0 v)";
+    let w = get_last_result(program);
+    assert_eq!(w.to_string(), "#(0 #0=#(#0# #0#))");
+    let program = "
+        (define w (make-vector 1 0))
+        (vector-set! w 0 w)
+        w";
+    assert_eq!(get_last_result(program), v);
+    assert_ne!(get_last_result(program), w);
+    let env = Environment::new();
+    for expr in parse_ceceo("(define v (make-vector 1 0)) (vector-set! v 0 v)").unwrap() {
+        eval_node(&expr, &env).unwrap();
+    }
+    let err = eval_node(&parse_ceceo("`(,v)").unwrap()[0], &env).unwrap_err();
+    assert!(matches!(
+        err.kind,
+        ErrorKind::Type {
+            expected: "datum",
+            ..
+        }
+    ));
+}
+
+#[test]
+fn arguments_hide_outer_variables() {
+    let program = "
+        (define x 'outer)
+        (define f (lambda (x) (if x '(x) x)))
+        (f #f)";
+    assert_eq!(
+        get_last_result(program),
+        EvalResult::Atom(Atom::Bool(false))
+    );
+    assert_eq!(
+        get_last_result("((lambda (x) (if x '(x) x)) 1)"),
+        EvalResult::QuoteList(parse_ceceo("x").unwrap())
+    );
+    assert_eq!(
+        get_last_result("(define x 'outer) ((lambda (x) x) 1) x"),
+        EvalResult::QuoteAtom(Atom::Symbol("outer".to_string()))
+    );
+    assert_eq!(
+        get_last_result("((lambda (f) (f 1 2)) +)"),
+        EvalResult::Atom(Atom::Num(3.into()))
+    );
+}
+
+#[test]
+fn errors_are_typed() {
+    let err = get_runtime_error("(+ 1 \"2\")");
+    assert_eq!(
+        err.kind,
+        ErrorKind::Type {
+            expected: "number",
+            got: "2".to_string()
+        }
+    );
+
+    let err = get_runtime_error("((lambda (x y) x) 1)");
+    assert_eq!(
+        err.kind,
+        ErrorKind::Arity {
+            expected: Arity::Exactly(2),
+            got: 1
+        }
+    );
+    assert_eq!(err.expr.unwrap().to_string(), "((lambda (x y) x) 1)");
+    let err = get_runtime_error("(if #t 1)");
+    assert_eq!(
+        err.to_string(),
+        "Arity mismatch: Expected 3, got 2 instead in (if #t 1)"
+    );
+
+    let err = get_runtime_error("(* 2 (+ x 1))");
+    assert_eq!(err.kind, ErrorKind::UnboundVariable("x".to_string()));
+    assert_eq!(err.expr.unwrap().to_string(), "(+ x 1)");
+    let err = get_runtime_error("(frobnicate 1)");
+    assert_eq!(
+        err.kind,
+        ErrorKind::UnboundVariable("frobnicate".to_string())
+    );
+}
+
+#[test]
+fn calling_a_value_that_isnt_a_procedure_is_an_error() {
+    let err = get_runtime_error("(1 2)");
+    assert_eq!(err.kind, ErrorKind::NotAProcedure("1".to_string()));
+    let err = get_runtime_error("(((lambda () 'a)) 1)");
+    assert_eq!(err.kind, ErrorKind::NotAProcedure("a".to_string()));
+    let err = get_runtime_error("((lambda (n) (n 1)) 5)");
+    assert_eq!(err.kind, ErrorKind::NotAProcedure("5".to_string()));
 }
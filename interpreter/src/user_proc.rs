@@ -2,54 +2,100 @@ use std::{collections::HashMap, hash::Hash};
 
 use parser::ast::Node;
 
+use crate::{environment::Environment, runtime_error::RuntimeError};
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct UserProc {
     arg_names: HashMap<String, usize>,
     body: Vec<Node>,
     quote_start: Option<usize>,
+    rest_arg: Option<String>,
+    /// Where the procedure was created, its body is evaluated in a frame
+    /// nested in it
+    env: Environment,
 }
 
 impl Hash for UserProc {
     fn hash<H: ~const std::hash::Hasher>(&self, state: &mut H) {
-        for arg in self.arg_names.iter() {
-            arg.hash(state)
+        for arg in &self.arg_names {
+            arg.hash(state);
         }
+        self.rest_arg.hash(state);
         self.body.hash(state);
     }
 }
 
 impl UserProc {
-    pub fn new(args: Vec<String>, body: Vec<Node>) -> UserProc {
-        let mut res = UserProc {
+    /// Fails when an argument name is repeated, as in `(lambda (x x) ...)`
+    pub(crate) fn new(
+        args: Vec<String>,
+        body: Vec<Node>,
+        env: Environment,
+    ) -> Result<Self, RuntimeError> {
+        let mut res = Self {
             arg_names: HashMap::new(),
             body,
             quote_start: None,
+            rest_arg: None,
+            env,
         };
 
         for (idx, arg) in args.into_iter().enumerate() {
-            res.arg_names.insert(arg, idx);
+            res.add_arg(arg, idx)?;
+        }
+        Ok(res)
+    }
+
+    fn add_arg(&mut self, name: String, idx: usize) -> Result<(), RuntimeError> {
+        if self.arg_names.contains_key(&name) {
+            return Err(RuntimeError::bad_syntax(format!(
+                "Incorrect lambda syntax: Duplicate argument {name}"
+            )));
         }
-        res
+
+        self.arg_names.insert(name, idx);
+        Ok(())
     }
 
-    pub fn quote_starts_at(mut self, idx: usize) -> UserProc {
+    pub(crate) const fn quote_starts_at(mut self, idx: usize) -> Self {
         self.quote_start = Some(idx);
         self
     }
 
+    /// Takes the arguments after the named ones as a list, like `rest` in
+    /// `(lambda (a . rest) ...)`.
+    pub(crate) fn with_rest_arg(mut self, name: String) -> Result<Self, RuntimeError> {
+        self.add_arg(name.clone(), self.arg_names.len())?;
+        self.rest_arg = Some(name);
+        Ok(self)
+    }
+
+    /// Number of named arguments, not counting the rest argument.
+    #[must_use]
     pub fn get_arity(&self) -> usize {
-        self.arg_names.len()
+        self.arg_names.len() - usize::from(self.rest_arg.is_some())
+    }
+
+    #[must_use]
+    pub const fn is_variadic(&self) -> bool {
+        self.rest_arg.is_some()
     }
 
+    #[must_use]
     pub fn get_body(&self) -> &[Node] {
         &self.body
     }
 
-    pub fn get_args(&self) -> &HashMap<String, usize> {
+    pub(crate) const fn get_args(&self) -> &HashMap<String, usize> {
         &self.arg_names
     }
 
-    pub fn is_quote_list_result(&self) -> Option<usize> {
+    pub(crate) const fn get_env(&self) -> &Environment {
+        &self.env
+    }
+
+    #[must_use]
+    pub const fn is_quote_list_result(&self) -> Option<usize> {
         self.quote_start
     }
 }
@@ -0,0 +1,51 @@
+use bimap::BiHashMap;
+use std::sync::LazyLock;
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub enum VectorProcs {
+    IsVector,
+    MakeVector,
+    Vector,
+    VectorLength,
+    VectorRef,
+    VectorSet,
+    VectorFill,
+    VectorToList,
+    ListToVector,
+    IsBytevector,
+    MakeBytevector,
+    Bytevector,
+    BytevectorLength,
+    BytevectorU8Ref,
+    BytevectorU8Set,
+}
+
+static VECTOR_PROCS_MAP: LazyLock<BiHashMap<VectorProcs, &'static str>> = LazyLock::new(|| {
+    BiHashMap::from_iter([
+        (VectorProcs::IsVector, "vector?"),
+        (VectorProcs::MakeVector, "make-vector"),
+        (VectorProcs::Vector, "vector"),
+        (VectorProcs::VectorLength, "vector-length"),
+        (VectorProcs::VectorRef, "vector-ref"),
+        (VectorProcs::VectorSet, "vector-set!"),
+        (VectorProcs::VectorFill, "vector-fill!"),
+        (VectorProcs::VectorToList, "vector->list"),
+        (VectorProcs::ListToVector, "list->vector"),
+        (VectorProcs::IsBytevector, "bytevector?"),
+        (VectorProcs::MakeBytevector, "make-bytevector"),
+        (VectorProcs::Bytevector, "bytevector"),
+        (VectorProcs::BytevectorLength, "bytevector-length"),
+        (VectorProcs::BytevectorU8Ref, "bytevector-u8-ref"),
+        (VectorProcs::BytevectorU8Set, "bytevector-u8-set!"),
+    ])
+});
+
+impl<'a> TryFrom<&'a str> for VectorProcs {
+    type Error = &'static str;
+    fn try_from(c: &'a str) -> Result<Self, Self::Error> {
+        VECTOR_PROCS_MAP
+            .get_by_right(c)
+            .cloned()
+            .ok_or("Unknown operator")
+    }
+}
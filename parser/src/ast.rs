@@ -1,24 +1,441 @@
+use std::fmt::Display;
+
+use crate::{
+    escape::escape_str,
+    lexer::LexicalError,
+    number::{parse_number, LiteralError, Number},
+    span::Span,
+};
+
 #[derive(Clone, Hash, Debug, PartialEq)]
 pub enum Node {
     Atom(Atom),
-    List(Vec<Node>),
-    QuoteList(Vec<Node>),
+    List(Vec<Self>),
+    QuoteList(Vec<Self>),
     QuoteAtom(Atom),
+    /// An improper list `(a b . c)`: the items before the dot and the tail after it
+    DottedList(Vec<Self>, Box<Self>),
+    QuoteDottedList(Vec<Self>, Box<Self>),
+    /// `#(a b c)`, its items are data and aren't evaluated
+    Vector(Vec<Self>),
+    /// `#u8(1 2 3)`
+    Bytevector(Vec<u8>),
+    /// `` `datum ``: quoted, except for the parts marked with `,` or `,@`
+    Quasiquote(Box<Self>),
+    /// `,datum`: evaluated inside a quasiquote
+    Unquote(Box<Self>),
+    /// `,@datum`: evaluated inside a quasiquote and spliced into the enclosing list
+    UnquoteSplicing(Box<Self>),
 }
 
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
 pub enum Atom {
-    Num(i32),
+    Num(Number),
     Symbol(String),
     Str(String),
     Bool(bool),
+    Char(char),
+}
+
+/// The kind of bracket a list was written with. All kinds mean the same,
+/// but a list must be closed with the kind it was opened with.
+#[derive(Copy, Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub enum Bracket {
+    #[default]
+    Round,
+    Square,
+    Curly,
+}
+
+impl Bracket {
+    #[must_use]
+    pub const fn from_open_char(ch: char) -> Option<Self> {
+        match ch {
+            '(' => Some(Self::Round),
+            '[' => Some(Self::Square),
+            '{' => Some(Self::Curly),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub const fn from_close_char(ch: char) -> Option<Self> {
+        match ch {
+            ')' => Some(Self::Round),
+            ']' => Some(Self::Square),
+            '}' => Some(Self::Curly),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub const fn open_char(self) -> char {
+        match self {
+            Self::Round => '(',
+            Self::Square => '[',
+            Self::Curly => '{',
+        }
+    }
+
+    #[must_use]
+    pub const fn close_char(self) -> char {
+        match self {
+            Self::Round => ')',
+            Self::Square => ']',
+            Self::Curly => '}',
+        }
+    }
+}
+
+/// Writes nodes back as source text, re-encoding string escapes.
+impl Display for Node {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn write_list(
+            f: &mut std::fmt::Formatter<'_>,
+            list: &[Node],
+            tail: Option<&Node>,
+        ) -> std::fmt::Result {
+            write!(f, "(")?;
+            for (idx, node) in list.iter().enumerate() {
+                if idx > 0 {
+                    write!(f, " ")?;
+                }
+                write!(f, "{node}")?;
+            }
+            if let Some(tail) = tail {
+                write!(f, " . {tail}")?;
+            }
+            write!(f, ")")
+        }
+
+        match self {
+            Self::Atom(a) => write!(f, "{a}"),
+            Self::List(l) => write_list(f, l, None),
+            Self::QuoteList(ql) => {
+                write!(f, "'")?;
+                write_list(f, ql, None)
+            }
+            Self::DottedList(l, tail) => write_list(f, l, Some(tail)),
+            Self::Vector(v) => {
+                write!(f, "#")?;
+                write_list(f, v, None)
+            }
+            Self::Bytevector(bytes) => {
+                write!(f, "#u8(")?;
+                for (idx, byte) in bytes.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{byte}")?;
+                }
+                write!(f, ")")
+            }
+            Self::QuoteDottedList(ql, tail) => {
+                write!(f, "'")?;
+                write_list(f, ql, Some(tail))
+            }
+            Self::QuoteAtom(qa) => write!(f, "'{qa}"),
+            Self::Quasiquote(node) => write!(f, "`{node}"),
+            Self::Unquote(node) => write!(f, ",{node}"),
+            Self::UnquoteSplicing(node) => write!(f, ",@{node}"),
+        }
+    }
+}
+
+impl Display for Atom {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Num(n) => write!(f, "{n}"),
+            Self::Symbol(s) => write!(f, "{s}"),
+            Self::Str(s) => write!(f, "\"{}\"", escape_str(s)),
+            Self::Bool(true) => write!(f, "#t"),
+            Self::Bool(false) => write!(f, "#f"),
+            Self::Char(c) => match CHAR_NAMES.iter().find(|(_, named)| named == c) {
+                Some((name, _)) => write!(f, "#\\{name}"),
+                None if c.is_control() => write!(f, "#\\x{:X}", *c as u32),
+                None => write!(f, "#\\{c}"),
+            },
+        }
+    }
+}
+
+/// A `Node` that remembers where it came from in the source.
+#[derive(Clone, Hash, Debug, PartialEq)]
+pub struct SpannedNode {
+    pub kind: SpannedKind,
+    pub span: Span,
+}
+
+#[derive(Clone, Hash, Debug, PartialEq)]
+pub enum SpannedKind {
+    Atom(Atom),
+    List(Bracket, Vec<SpannedNode>),
+    QuoteList(Bracket, Vec<SpannedNode>),
+    QuoteAtom(Atom),
+    DottedList(Bracket, Vec<SpannedNode>, Box<SpannedNode>),
+    QuoteDottedList(Bracket, Vec<SpannedNode>, Box<SpannedNode>),
+    Vector(Bracket, Vec<SpannedNode>),
+    Bytevector(Bracket, Vec<u8>),
+    Quasiquote(Box<SpannedNode>),
+    Unquote(Box<SpannedNode>),
+    UnquoteSplicing(Box<SpannedNode>),
 }
 
-#[must_use]
-pub fn parse_hash_symbol(s: &str) -> Atom {
+impl SpannedKind {
+    /// A list, or an improper list when there's a `tail` after the dot.
+    #[must_use]
+    pub fn list(bracket: Bracket, items: Vec<SpannedNode>, tail: Option<SpannedNode>) -> Self {
+        match tail {
+            Some(tail) => Self::DottedList(bracket, items, Box::new(tail)),
+            None => Self::List(bracket, items),
+        }
+    }
+
+    /// `'datum`. A quoted atom or list keeps its items, vectors are data
+    /// already and stay as they are, and a quoted prefixed datum like `''a`
+    /// is the list it stands for, `(quote a)`.
+    #[must_use]
+    pub fn quote(datum: SpannedNode) -> Self {
+        let span = datum.span;
+        let tagged = |tag: &str, node: SpannedNode| {
+            let tag = SpannedNode::new(Self::Atom(Atom::Symbol(tag.to_string())), span);
+            Self::QuoteList(Bracket::Round, vec![tag, node])
+        };
+        let inner = |kind: Self| SpannedNode::new(kind, span);
+
+        return match datum.kind {
+            Self::Atom(a) => Self::QuoteAtom(a),
+            Self::List(b, l) => Self::QuoteList(b, l),
+            Self::DottedList(b, l, tail) => Self::QuoteDottedList(b, l, tail),
+            kind @ (Self::Vector(..) | Self::Bytevector(..)) => kind,
+            Self::QuoteAtom(a) => tagged("quote", inner(Self::Atom(a))),
+            Self::QuoteList(b, l) => tagged("quote", inner(Self::List(b, l))),
+            Self::QuoteDottedList(b, l, tail) => {
+                tagged("quote", inner(Self::DottedList(b, l, tail)))
+            }
+            Self::Quasiquote(node) => tagged("quasiquote", *node),
+            Self::Unquote(node) => tagged("unquote", *node),
+            Self::UnquoteSplicing(node) => tagged("unquote-splicing", *node),
+        };
+    }
+}
+
+impl SpannedNode {
+    #[must_use]
+    pub const fn new(kind: SpannedKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+
+    /// Child nodes of a list or quoted list, the datum under a quasiquote
+    /// or unquote, empty for atoms. For an improper list these are the items
+    /// before the dot, see `tail`.
+    #[must_use]
+    pub fn children(&self) -> &[Self] {
+        match &self.kind {
+            SpannedKind::List(_, list)
+            | SpannedKind::QuoteList(_, list)
+            | SpannedKind::DottedList(_, list, _)
+            | SpannedKind::QuoteDottedList(_, list, _)
+            | SpannedKind::Vector(_, list) => list,
+            SpannedKind::Quasiquote(node)
+            | SpannedKind::Unquote(node)
+            | SpannedKind::UnquoteSplicing(node) => std::slice::from_ref(node),
+            SpannedKind::Atom(_) | SpannedKind::QuoteAtom(_) | SpannedKind::Bytevector(..) => &[],
+        }
+    }
+
+    /// The datum after the dot of an improper list.
+    #[must_use]
+    pub fn tail(&self) -> Option<&Self> {
+        match &self.kind {
+            SpannedKind::DottedList(_, _, tail) | SpannedKind::QuoteDottedList(_, _, tail) => {
+                Some(tail)
+            }
+            _ => None,
+        }
+    }
+
+    /// Strips the spans, producing the bare tree used by the evaluator.
+    #[must_use]
+    pub fn to_node(&self) -> Node {
+        fn to_nodes(list: &[SpannedNode]) -> Vec<Node> {
+            list.iter().map(SpannedNode::to_node).collect()
+        }
+
+        match &self.kind {
+            SpannedKind::Atom(a) => Node::Atom(a.clone()),
+            SpannedKind::List(_, l) => Node::List(to_nodes(l)),
+            SpannedKind::QuoteList(_, ql) => Node::QuoteList(to_nodes(ql)),
+            SpannedKind::QuoteAtom(qa) => Node::QuoteAtom(qa.clone()),
+            SpannedKind::Vector(_, v) => Node::Vector(to_nodes(v)),
+            SpannedKind::Bytevector(_, bytes) => Node::Bytevector(bytes.clone()),
+            SpannedKind::DottedList(_, l, tail) => {
+                Node::DottedList(to_nodes(l), Box::new(tail.to_node()))
+            }
+            SpannedKind::QuoteDottedList(_, ql, tail) => {
+                Node::QuoteDottedList(to_nodes(ql), Box::new(tail.to_node()))
+            }
+            SpannedKind::Quasiquote(node) => Node::Quasiquote(Box::new(node.to_node())),
+            SpannedKind::Unquote(node) => Node::Unquote(Box::new(node.to_node())),
+            SpannedKind::UnquoteSplicing(node) => Node::UnquoteSplicing(Box::new(node.to_node())),
+        }
+    }
+
+    /// A node built outside of the parser, like the expansion of a custom
+    /// `#name` literal, with every part of it given `span`.
+    #[must_use]
+    pub fn from_node(node: &Node, span: Span) -> Self {
+        let from_nodes = |nodes: &[Node]| -> Vec<Self> {
+            nodes
+                .iter()
+                .map(|node| Self::from_node(node, span))
+                .collect()
+        };
+        let from_box = |node: &Node| Box::new(Self::from_node(node, span));
+
+        let kind = match node {
+            Node::Atom(a) => SpannedKind::Atom(a.clone()),
+            Node::List(l) => SpannedKind::List(Bracket::Round, from_nodes(l)),
+            Node::QuoteList(ql) => SpannedKind::QuoteList(Bracket::Round, from_nodes(ql)),
+            Node::QuoteAtom(qa) => SpannedKind::QuoteAtom(qa.clone()),
+            Node::DottedList(l, tail) => {
+                SpannedKind::DottedList(Bracket::Round, from_nodes(l), from_box(tail))
+            }
+            Node::QuoteDottedList(ql, tail) => {
+                SpannedKind::QuoteDottedList(Bracket::Round, from_nodes(ql), from_box(tail))
+            }
+            Node::Vector(v) => SpannedKind::Vector(Bracket::Round, from_nodes(v)),
+            Node::Bytevector(bytes) => SpannedKind::Bytevector(Bracket::Round, bytes.clone()),
+            Node::Quasiquote(node) => SpannedKind::Quasiquote(from_box(node)),
+            Node::Unquote(node) => SpannedKind::Unquote(from_box(node)),
+            Node::UnquoteSplicing(node) => SpannedKind::UnquoteSplicing(from_box(node)),
+        };
+
+        return Self::new(kind, span);
+    }
+
+    /// The same node in a text that has `offset` more bytes before it, used
+    /// when a piece of a larger source is parsed on its own.
+    #[must_use]
+    pub fn shifted(self, offset: usize) -> Self {
+        fn shift_all(list: Vec<SpannedNode>, offset: usize) -> Vec<SpannedNode> {
+            list.into_iter().map(|node| node.shifted(offset)).collect()
+        }
+        let shift_box = |node: Box<Self>| Box::new(node.shifted(offset));
+
+        let kind = match self.kind {
+            kind @ (SpannedKind::Atom(_)
+            | SpannedKind::QuoteAtom(_)
+            | SpannedKind::Bytevector(..)) => kind,
+            SpannedKind::List(b, l) => SpannedKind::List(b, shift_all(l, offset)),
+            SpannedKind::QuoteList(b, ql) => SpannedKind::QuoteList(b, shift_all(ql, offset)),
+            SpannedKind::Vector(b, v) => SpannedKind::Vector(b, shift_all(v, offset)),
+            SpannedKind::DottedList(b, l, tail) => {
+                SpannedKind::DottedList(b, shift_all(l, offset), shift_box(tail))
+            }
+            SpannedKind::QuoteDottedList(b, ql, tail) => {
+                SpannedKind::QuoteDottedList(b, shift_all(ql, offset), shift_box(tail))
+            }
+            SpannedKind::Quasiquote(node) => SpannedKind::Quasiquote(shift_box(node)),
+            SpannedKind::Unquote(node) => SpannedKind::Unquote(shift_box(node)),
+            SpannedKind::UnquoteSplicing(node) => SpannedKind::UnquoteSplicing(shift_box(node)),
+        };
+
+        return Self::new(kind, self.span.shifted(offset));
+    }
+}
+
+impl From<SpannedNode> for Node {
+    fn from(node: SpannedNode) -> Self {
+        node.to_node()
+    }
+}
+
+/// Named characters, written as `#\name`.
+const CHAR_NAMES: [(&str, char); 10] = [
+    ("space", ' '),
+    ("newline", '\n'),
+    ("tab", '\t'),
+    ("return", '\r'),
+    ("null", '\0'),
+    ("alarm", '\u{7}'),
+    ("backspace", '\u{8}'),
+    ("escape", '\u{1B}'),
+    ("delete", '\u{7F}'),
+    ("nul", '\0'),
+];
+
+pub fn parse_hash_symbol(s: &str, span: Span) -> Result<Atom, LexicalError> {
     match s {
-        "#true" | "#t" | "#T" => Atom::Bool(true),
-        "#false" | "#f" | "#F" => Atom::Bool(false),
-        _ => panic!("Unknown hash symbol"),
+        "#true" | "#t" | "#T" => Ok(Atom::Bool(true)),
+        "#false" | "#f" | "#F" => Ok(Atom::Bool(false)),
+        _ if s.starts_with("#\\") => parse_char_name(&s[2..])
+            .map(Atom::Char)
+            .ok_or(LexicalError::UnknownCharName { span }),
+        _ => Err(LexicalError::UnknownHashLiteral { span }),
+    }
+}
+
+/// The char written after `#\`: a single char, a name like `space`, or a
+/// hex scalar value like `x3BB`.
+fn parse_char_name(name: &str) -> Option<char> {
+    let mut chars = name.chars();
+    let first = chars.next()?;
+    if chars.next().is_none() {
+        return Some(first);
+    }
+
+    let named = CHAR_NAMES.iter().find(|(char_name, _)| *char_name == name);
+    if let Some(&(_, c)) = named {
+        return Some(c);
+    }
+
+    let digits = name
+        .strip_prefix('x')
+        .filter(|digits| digits.chars().all(|c| c.is_ascii_hexdigit()))?;
+    u32::from_str_radix(digits, 16)
+        .ok()
+        .and_then(char::from_u32)
+}
+
+pub fn parse_byte(s: &str, span: Span) -> Result<u8, LexicalError> {
+    parse_number(s)
+        .ok()
+        .as_ref()
+        .and_then(Number::as_integer)
+        .and_then(|n| u8::try_from(n).ok())
+        .ok_or(LexicalError::NumberOutOfRange { span })
+}
+
+pub fn parse_num(s: &str, span: Span) -> Result<Atom, LexicalError> {
+    parse_number(s).map(Atom::Num).map_err(|err| match err {
+        LiteralError::Malformed => LexicalError::MalformedNumber { span },
+        LiteralError::OutOfRange => LexicalError::NumberOutOfRange { span },
+    })
+}
+
+#[test]
+fn char_literals() {
+    let parse = |s: &str| parse_hash_symbol(s, Span::new(0, s.len()));
+
+    assert_eq!(parse("#\\a"), Ok(Atom::Char('a')));
+    assert_eq!(parse("#\\("), Ok(Atom::Char('(')));
+    assert_eq!(parse("#\\x"), Ok(Atom::Char('x')));
+    assert_eq!(parse("#\\space"), Ok(Atom::Char(' ')));
+    assert_eq!(parse("#\\newline"), Ok(Atom::Char('\n')));
+    assert_eq!(parse("#\\x3BB"), Ok(Atom::Char('λ')));
+    assert_eq!(parse("#\\λ"), Ok(Atom::Char('λ')));
+    assert_eq!(
+        parse("#\\spaces"),
+        Err(LexicalError::UnknownCharName {
+            span: Span::new(0, 8)
+        })
+    );
+    assert!(parse("#\\x+41").is_err());
+    assert!(parse("#\\xD800").is_err());
+
+    for c in ['a', ' ', '\n', 'λ', '\u{1}', '('] {
+        let written = Atom::Char(c).to_string();
+        assert_eq!(parse(&written), Ok(Atom::Char(c)), "{written}");
     }
 }
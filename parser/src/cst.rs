@@ -0,0 +1,560 @@
+use std::{fmt::Display, iter::Peekable};
+
+use lalrpop_util::ParseError;
+
+use crate::{
+    ast::{
+        parse_byte, parse_hash_symbol, parse_num, Atom, Bracket, Node, SpannedKind, SpannedNode,
+    },
+    dispatch::DispatchTable,
+    escape::unescape_str,
+    lexer::{Lexer, Tok},
+    parse_ceceo_spanned_with,
+    span::Span,
+    CeceoParseError,
+};
+
+/// A concrete syntax tree: every byte of the source is kept, either in a
+/// node or in the trivia around it, so printing it gives back the input.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cst<'input> {
+    pub nodes: Vec<CstNode<'input>>,
+    /// Whitespace and comments after the last node.
+    pub trailing: Vec<Trivia<'input>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CstNode<'input> {
+    /// Whitespace and comments between the previous token and this node.
+    pub leading: Vec<Trivia<'input>>,
+    pub kind: CstKind<'input>,
+    /// Span of the node itself, without its leading trivia.
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CstKind<'input> {
+    /// An atom together with the text it was written as.
+    Atom {
+        atom: Atom,
+        text: &'input str,
+    },
+    List {
+        bracket: Bracket,
+        items: Vec<CstNode<'input>>,
+        /// The dot and tail of an improper list.
+        dot: Option<CstDot<'input>>,
+        /// Whitespace and comments before the closing bracket.
+        trailing: Vec<Trivia<'input>>,
+    },
+    /// `#(...)`, or `#u8(...)` when `bytes` is set, in which case every
+    /// item is a number atom that fits in a byte.
+    Vector {
+        bytes: bool,
+        bracket: Bracket,
+        items: Vec<CstNode<'input>>,
+        trailing: Vec<Trivia<'input>>,
+    },
+    Quote(Box<CstNode<'input>>),
+    Quasiquote(Box<CstNode<'input>>),
+    Unquote(Box<CstNode<'input>>),
+    UnquoteSplicing(Box<CstNode<'input>>),
+    /// A custom `#name` literal, with the datum after the name and the node
+    /// the literal reads as.
+    Dispatch {
+        tag: &'input str,
+        datum: Box<CstNode<'input>>,
+        expansion: Node,
+    },
+}
+
+/// The `. tail` part of an improper list.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CstDot<'input> {
+    /// Whitespace and comments before the dot.
+    pub leading: Vec<Trivia<'input>>,
+    pub span: Span,
+    pub tail: Box<CstNode<'input>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Trivia<'input> {
+    pub kind: TriviaKind,
+    pub text: &'input str,
+    pub span: Span,
+}
+
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum TriviaKind {
+    Whitespace,
+    /// `; ...` up to the end of the line
+    LineComment,
+    /// `#| ... |#`
+    BlockComment,
+    /// `#;` followed by the datum it comments out
+    DatumComment,
+}
+
+impl Cst<'_> {
+    /// Drops the trivia, producing the same tree as `parse_ceceo`.
+    #[must_use]
+    pub fn to_nodes(&self) -> Vec<Node> {
+        self.nodes.iter().map(CstNode::to_node).collect()
+    }
+}
+
+impl CstNode<'_> {
+    fn to_nodes(nodes: &[Self]) -> Vec<Node> {
+        nodes.iter().map(Self::to_node).collect()
+    }
+
+    fn vector_to_node(&self) -> Node {
+        match &self.kind {
+            CstKind::Vector {
+                bytes: true, items, ..
+            } => Node::Bytevector(
+                items
+                    .iter()
+                    .filter_map(|item| match item.kind {
+                        CstKind::Atom {
+                            atom: Atom::Num(ref n),
+                            ..
+                        } => n.as_integer().and_then(|n| u8::try_from(n).ok()),
+                        _ => None,
+                    })
+                    .collect(),
+            ),
+            CstKind::Vector { items, .. } => Node::Vector(Self::to_nodes(items)),
+            _ => unreachable!("only called on vectors"),
+        }
+    }
+
+    #[must_use]
+    pub fn to_node(&self) -> Node {
+        match &self.kind {
+            CstKind::Atom { atom, .. } => Node::Atom(atom.clone()),
+            CstKind::List {
+                items, dot: None, ..
+            } => Node::List(Self::to_nodes(items)),
+            CstKind::List {
+                items,
+                dot: Some(dot),
+                ..
+            } => Node::DottedList(Self::to_nodes(items), Box::new(dot.tail.to_node())),
+            CstKind::Vector { .. } => self.vector_to_node(),
+            CstKind::Quote(quoted) => {
+                let datum = SpannedNode::from_node(&quoted.to_node(), quoted.span);
+                SpannedNode::new(SpannedKind::quote(datum), self.span).to_node()
+            }
+            CstKind::Quasiquote(node) => Node::Quasiquote(Box::new(node.to_node())),
+            CstKind::Unquote(node) => Node::Unquote(Box::new(node.to_node())),
+            CstKind::UnquoteSplicing(node) => Node::UnquoteSplicing(Box::new(node.to_node())),
+            CstKind::Dispatch { expansion, .. } => expansion.clone(),
+        }
+    }
+}
+
+/// Writes the tree back out exactly as it was parsed.
+impl Display for Cst<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for node in &self.nodes {
+            write!(f, "{node}")?;
+        }
+        write_trivia(f, &self.trailing)
+    }
+}
+
+impl Display for CstNode<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write_trivia(f, &self.leading)?;
+        match &self.kind {
+            CstKind::Atom { text, .. } => write!(f, "{text}"),
+            CstKind::List {
+                bracket,
+                items,
+                dot,
+                trailing,
+            } => {
+                write!(f, "{}", bracket.open_char())?;
+                for item in items {
+                    write!(f, "{item}")?;
+                }
+                if let Some(dot) = dot {
+                    write_trivia(f, &dot.leading)?;
+                    write!(f, ".{}", dot.tail)?;
+                }
+                write_trivia(f, trailing)?;
+                write!(f, "{}", bracket.close_char())
+            }
+            CstKind::Vector {
+                bytes,
+                bracket,
+                items,
+                trailing,
+            } => {
+                let prefix = if *bytes { "#u8" } else { "#" };
+                write!(f, "{prefix}{}", bracket.open_char())?;
+                for item in items {
+                    write!(f, "{item}")?;
+                }
+                write_trivia(f, trailing)?;
+                write!(f, "{}", bracket.close_char())
+            }
+            CstKind::Quote(quoted) => write!(f, "'{quoted}"),
+            CstKind::Quasiquote(node) => write!(f, "`{node}"),
+            CstKind::Unquote(node) => write!(f, ",{node}"),
+            CstKind::UnquoteSplicing(node) => write!(f, ",@{node}"),
+            CstKind::Dispatch { tag, datum, .. } => write!(f, "{tag}{datum}"),
+        }
+    }
+}
+
+fn write_trivia(f: &mut std::fmt::Formatter<'_>, trivia: &[Trivia<'_>]) -> std::fmt::Result {
+    for t in trivia {
+        write!(f, "{}", t.text)?;
+    }
+    Ok(())
+}
+
+/// Parses `input` into a lossless tree. Accepts exactly the programs
+/// `parse_ceceo` accepts.
+pub fn parse(input: &str) -> Result<Cst<'_>, CeceoParseError<'_>> {
+    parse_with(input, &DispatchTable::new())
+}
+
+/// Like `parse`, also reading the custom `#name` literals of `dispatch`.
+///
+/// The input is first checked by the generated parser, whose errors are
+/// returned as they are, so the tree is only built from well-formed
+/// programs.
+pub fn parse_with<'input>(
+    input: &'input str,
+    dispatch: &DispatchTable,
+) -> Result<Cst<'input>, CeceoParseError<'input>> {
+    parse_ceceo_spanned_with(input, dispatch)?;
+
+    let mut parser = CstParser {
+        input,
+        tokens: Lexer::with_trivia(input).peekable(),
+        dispatch,
+    };
+
+    let mut nodes = Vec::new();
+    loop {
+        let leading = parser.trivia()?;
+        if parser.peek()?.is_none() {
+            return Ok(Cst {
+                nodes,
+                trailing: leading,
+            });
+        }
+        nodes.push(parser.node(leading)?);
+    }
+}
+
+struct CstParser<'input, 'table> {
+    input: &'input str,
+    tokens: Peekable<Lexer<'input>>,
+    dispatch: &'table DispatchTable,
+}
+
+type Token<'input> = (usize, Tok<'input>, usize);
+
+// The tokens are only read once the grammar accepted them, so running out of
+// them or finding one out of place is a bug
+const ACCEPTED: &str = "the grammar accepted the input";
+
+impl<'input> CstParser<'input, '_> {
+    fn peek(&mut self) -> Result<Option<&Token<'input>>, CeceoParseError<'input>> {
+        match self.tokens.peek() {
+            Some(Err(error)) => Err(ParseError::User {
+                error: error.clone(),
+            }),
+            Some(Ok(token)) => Ok(Some(token)),
+            None => Ok(None),
+        }
+    }
+
+    fn trivia(&mut self) -> Result<Vec<Trivia<'input>>, CeceoParseError<'input>> {
+        let mut trivia = Vec::new();
+        while let Some(&(start, tok, end)) = self.peek()? {
+            let kind = match tok {
+                Tok::Whitespace => TriviaKind::Whitespace,
+                Tok::Comment(text) if text.starts_with(';') => TriviaKind::LineComment,
+                Tok::Comment(text) if text.starts_with("#|") => TriviaKind::BlockComment,
+                Tok::Comment(_) => TriviaKind::DatumComment,
+                _ => break,
+            };
+            self.tokens.next();
+            trivia.push(Trivia {
+                kind,
+                text: &self.input[start..end],
+                span: Span::new(start, end),
+            });
+        }
+
+        return Ok(trivia);
+    }
+
+    /// Parses the datum starting at the next token
+    fn node(
+        &mut self,
+        leading: Vec<Trivia<'input>>,
+    ) -> Result<CstNode<'input>, CeceoParseError<'input>> {
+        let (start, tok, end) = self.peek()?.copied().expect(ACCEPTED);
+        let user_error = |error| ParseError::User { error };
+
+        let kind = match tok {
+            Tok::Quote | Tok::Quasiquote | Tok::Unquote | Tok::UnquoteSplicing => {
+                self.tokens.next();
+                let prefixed_leading = self.trivia()?;
+                let prefixed = Box::new(self.node(prefixed_leading)?);
+                match tok {
+                    Tok::Quote => CstKind::Quote(prefixed),
+                    Tok::Quasiquote => CstKind::Quasiquote(prefixed),
+                    Tok::Unquote => CstKind::Unquote(prefixed),
+                    _ => CstKind::UnquoteSplicing(prefixed),
+                }
+            }
+            Tok::LeftParen(_) | Tok::VectorStart(_) | Tok::BytevectorStart(_) => {
+                self.tokens.next();
+                return self.list(leading, tok, start);
+            }
+            Tok::HashDispatch(tag) => {
+                self.tokens.next();
+                // The lexer only makes a `#name` token when a datum follows
+                // right away, so there's no trivia in between
+                let datum = Box::new(self.node(Vec::new())?);
+                let span = Span::new(start, datum.span.end);
+                let expansion = self
+                    .dispatch
+                    .expand(tag, &datum.to_node(), span)
+                    .map_err(user_error)?;
+                CstKind::Dispatch {
+                    tag,
+                    datum,
+                    expansion,
+                }
+            }
+            Tok::Symbol(s) => CstKind::Atom {
+                atom: Atom::Symbol(s.to_string()),
+                text: s,
+            },
+            Tok::HashSymbol(h) => CstKind::Atom {
+                atom: parse_hash_symbol(h, Span::new(start, end)).map_err(user_error)?,
+                text: h,
+            },
+            Tok::Str(s) => CstKind::Atom {
+                atom: unescape_str(s, start + 1)
+                    .map(Atom::Str)
+                    .map_err(user_error)?,
+                text: &self.input[start..end],
+            },
+            Tok::Num(n) => CstKind::Atom {
+                atom: parse_num(n, Span::new(start, end)).map_err(user_error)?,
+                text: n,
+            },
+            _ => unreachable!("{ACCEPTED}"),
+        };
+        if matches!(kind, CstKind::Atom { .. }) {
+            self.tokens.next();
+        }
+
+        let end = match &kind {
+            CstKind::Quote(prefixed)
+            | CstKind::Quasiquote(prefixed)
+            | CstKind::Unquote(prefixed)
+            | CstKind::UnquoteSplicing(prefixed)
+            | CstKind::Dispatch {
+                datum: prefixed, ..
+            } => prefixed.span.end,
+            _ => end,
+        };
+        return Ok(CstNode {
+            leading,
+            kind,
+            span: Span::new(start, end),
+        });
+    }
+
+    /// Parses the items of a list or vector opened by `open`, up to and
+    /// including the closing bracket.
+    fn list(
+        &mut self,
+        leading: Vec<Trivia<'input>>,
+        open: Tok<'input>,
+        start: usize,
+    ) -> Result<CstNode<'input>, CeceoParseError<'input>> {
+        let mut items = Vec::new();
+        let mut dot = None;
+        let mut trailing = self.trivia()?;
+        let is_bytevector = matches!(open, Tok::BytevectorStart(_));
+        loop {
+            match self.peek()?.copied().expect(ACCEPTED) {
+                // The lexer makes sure the closing bracket matches the opening one
+                (_, Tok::RightParen(_), end) => {
+                    self.tokens.next();
+                    let kind = match open {
+                        Tok::VectorStart(bracket) | Tok::BytevectorStart(bracket) => {
+                            CstKind::Vector {
+                                bytes: is_bytevector,
+                                bracket,
+                                items,
+                                trailing,
+                            }
+                        }
+                        Tok::LeftParen(bracket) => CstKind::List {
+                            bracket,
+                            items,
+                            dot,
+                            trailing,
+                        },
+                        _ => unreachable!("lists are only opened by brackets"),
+                    };
+                    return Ok(CstNode {
+                        leading,
+                        kind,
+                        span: Span::new(start, end),
+                    });
+                }
+                (dot_start, Tok::Dot, dot_end) => {
+                    self.tokens.next();
+                    let tail_leading = self.trivia()?;
+                    dot = Some(CstDot {
+                        leading: trailing,
+                        span: Span::new(dot_start, dot_end),
+                        tail: Box::new(self.node(tail_leading)?),
+                    });
+                }
+                (num_start, Tok::Num(n), num_end) if is_bytevector => {
+                    self.tokens.next();
+                    let span = Span::new(num_start, num_end);
+                    let byte = parse_byte(n, span).map_err(|error| ParseError::User { error })?;
+                    items.push(CstNode {
+                        leading: trailing,
+                        kind: CstKind::Atom {
+                            atom: Atom::Num(byte.into()),
+                            text: n,
+                        },
+                        span,
+                    });
+                }
+                _ => items.push(self.node(trailing)?),
+            }
+            trailing = self.trivia()?;
+        }
+    }
+}
+
+#[test]
+fn cst_round_trips_source() {
+    let sources = [
+        "",
+        "   \n",
+        "; header\n(define (f x) ; trailing\n  [* x #| inline |# 2])\n\n#;(ignored form)\n(f 21)\n",
+        "(display \"a\\tb\\x41;\")\r\n'sym ' ( 1 2 ) {cond [#t 1]}",
+        "`(a ,b ,@ (c) ` ,'d)",
+        "(a . b) '(1 2 #| c |# . ; tail\n (3)) [x .y]",
+        "#(1 #(a) \"s\") #u8[ 0 255 ] '#() #u8()",
+        "''a '`(a ,b) ' ,@c",
+        "#t 42 -7 \"str\" ( ) ;; no newline at the end",
+    ];
+
+    for source in sources {
+        let cst = parse(source).unwrap();
+        assert_eq!(cst.to_string(), source);
+    }
+}
+
+#[test]
+fn cst_matches_ast() {
+    let source = "(define [sq x] (* x x))\n'(1 2) 'a\n; comment\n(sq #;(ignored) 3) ''a '`(a ,b)";
+    let cst = parse(source).unwrap();
+
+    assert_eq!(cst.to_nodes(), crate::parse_ceceo(source).unwrap());
+}
+
+#[test]
+fn cst_keeps_trivia_and_spans() {
+    let source = "(a ; note\n b)";
+    let cst = parse(source).unwrap();
+    let CstKind::List { bracket, items, .. } = &cst.nodes[0].kind else {
+        panic!("expected a list");
+    };
+
+    assert_eq!(*bracket, Bracket::Round);
+    assert_eq!(items[1].span, Span::new(11, 12));
+    let kinds: Vec<TriviaKind> = items[1].leading.iter().map(|t| t.kind).collect();
+    assert_eq!(
+        kinds,
+        [
+            TriviaKind::Whitespace,
+            TriviaKind::LineComment,
+            TriviaKind::Whitespace
+        ]
+    );
+}
+
+#[test]
+fn cst_rejects_what_the_parser_rejects() {
+    for source in [
+        "(a",
+        "a)",
+        "(",
+        ")",
+        "'",
+        "`",
+        ",",
+        ",@",
+        "`(a ,",
+        "(a]",
+        "[a)",
+        "{a b",
+        "(a) (b",
+        "(a) )",
+        ".",
+        "a . b",
+        "(.)",
+        "(. a)",
+        "(a .)",
+        "(a . b c)",
+        "(a . b . c)",
+        "'(a . )",
+        "#(a . b)",
+        "#(1 2",
+        "#u8(a)",
+        "#u8((1))",
+        "#u8(1 . 2)",
+        "#u8(1 256)",
+        "#u8(-1)",
+        "\"\\q\"",
+        "\"unterminated",
+        "#| unterminated",
+        "#;",
+        "(#;)",
+        "#what",
+        "#xyz",
+        "#\\nope",
+        "1/0",
+        "#e1e99999999",
+        "(define x",
+        "(define (f x) '(1 \"two\" #t -3)) (f [g {h}]",
+    ] {
+        assert_eq!(
+            parse(source).unwrap_err(),
+            crate::parse_ceceo(source).unwrap_err(),
+            "{source}"
+        );
+    }
+
+    // Every prefix of a program is accepted by both or rejected the same way
+    let sample = "(define [f . args] `(#(1 #u8(2)) ,@args . 'x)) ; done\n#;(f) {\"s\" #\\a}";
+    assert!(parse(sample).is_ok());
+    for prefix in (0..=sample.len()).filter_map(|idx| sample.get(..idx)) {
+        assert_eq!(
+            parse(prefix).map(|cst| cst.to_nodes()),
+            crate::parse_ceceo(prefix),
+            "{prefix}"
+        );
+    }
+}
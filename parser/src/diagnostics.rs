@@ -0,0 +1,210 @@
+use std::fmt::Write;
+
+use lalrpop_util::ParseError;
+
+use crate::{
+    lexer::LexicalError,
+    span::{LineIndex, Span},
+    CeceoParseError,
+};
+
+/// A parse error resolved to a message and a source region, ready to be shown to a user.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub message: String,
+    pub span: Span,
+    pub expected: Vec<String>,
+    /// Other source locations involved in the error, with a label for each.
+    pub related: Vec<(Span, String)>,
+}
+
+impl Diagnostic {
+    #[must_use]
+    pub fn from_parse_error(error: &CeceoParseError<'_>) -> Self {
+        let (message, span, expected) = match error {
+            ParseError::InvalidToken { location } => (
+                "invalid token".to_string(),
+                Span::new(*location, *location + 1),
+                &[][..],
+            ),
+            ParseError::UnrecognizedEOF { location, expected } => (
+                "unexpected end of input".to_string(),
+                Span::new(*location, *location),
+                expected.as_slice(),
+            ),
+            ParseError::UnrecognizedToken {
+                token: (start, tok, end),
+                expected,
+            } => (
+                format!("unexpected `{tok}`"),
+                Span::new(*start, *end),
+                expected.as_slice(),
+            ),
+            ParseError::ExtraToken {
+                token: (start, tok, end),
+            } => (
+                format!("unexpected extra `{tok}`"),
+                Span::new(*start, *end),
+                &[][..],
+            ),
+            ParseError::User { error } => (error.to_string(), error.span(), &[][..]),
+        };
+        let related = match error {
+            ParseError::User {
+                error:
+                    LexicalError::MismatchedBracket {
+                        open, open_kind, ..
+                    },
+            } => vec![(*open, format!("`{}` opened here", open_kind.open_char()))],
+            _ => Vec::new(),
+        };
+
+        return Self {
+            message,
+            span,
+            expected: expected.to_vec(),
+            related,
+        };
+    }
+
+    /// The same diagnostic in a text that has `offset` more bytes before it.
+    #[must_use]
+    pub fn shifted(mut self, offset: usize) -> Self {
+        self.span = self.span.shifted(offset);
+        for (span, _) in &mut self.related {
+            *span = span.shifted(offset);
+        }
+
+        return self;
+    }
+
+    /// Renders the diagnostic with its location, the offending source line and
+    /// a caret underline, e.g.
+    ///
+    /// ```text
+    /// error: unexpected `)`
+    ///  --> main.ceceo:1:8
+    ///   |
+    /// 1 | (+ 1 2))
+    ///   |        ^
+    ///   = expected one of "#(", "#u8(", "'", "(", ",", ",@", "`", HashDispatch, HashSymbol, Num, Str, Symbol
+    /// ```
+    #[must_use]
+    pub fn render(&self, file_name: &str, source: &str) -> String {
+        let index = LineIndex::new(source);
+        let start = index.line_col(self.span.start);
+        let end = index.line_col(self.span.end);
+        let line_text = index.line_text(start.line);
+
+        // Keep tabs so the caret lines up with the source line
+        let padding: String = line_text
+            .chars()
+            .take(start.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let underline_len = if end.line == start.line {
+            end.column - start.column
+        } else {
+            line_text.chars().count() + 1 - start.column
+        };
+        let underline = "^".repeat(underline_len.max(1));
+
+        let gutter = " ".repeat(start.line.to_string().len());
+        let mut out = String::new();
+        _ = writeln!(out, "error: {}", self.message);
+        _ = writeln!(
+            out,
+            "{gutter}--> {file_name}:{}:{}",
+            start.line, start.column
+        );
+        _ = writeln!(out, "{gutter} |");
+        _ = writeln!(out, "{} | {line_text}", start.line);
+        _ = write!(out, "{gutter} | {padding}{underline}");
+
+        match self.expected.as_slice() {
+            [] => (),
+            [single] => _ = write!(out, "\n{gutter} = expected {single}"),
+            many => _ = write!(out, "\n{gutter} = expected one of {}", many.join(", ")),
+        }
+
+        for (span, label) in &self.related {
+            let pos = index.line_col(span.start);
+            _ = write!(
+                out,
+                "\n{gutter} = note: {label} at {file_name}:{}:{}",
+                pos.line, pos.column
+            );
+        }
+
+        return out;
+    }
+}
+
+#[must_use]
+pub fn render_parse_error(file_name: &str, source: &str, error: &CeceoParseError<'_>) -> String {
+    Diagnostic::from_parse_error(error).render(file_name, source)
+}
+
+#[test]
+fn renders_unexpected_token() {
+    let source = "(display 1)\n(+ 1 2))";
+    let err = crate::parse_ceceo(source).unwrap_err();
+    let rendered = render_parse_error("main.ceceo", source, &err);
+
+    assert_eq!(
+        rendered,
+        "error: unexpected `)`
+ --> main.ceceo:2:8
+  |
+2 | (+ 1 2))
+  |        ^
+  = expected one of \"#(\", \"#u8(\", \"'\", \"(\", \",\", \",@\", \"`\", HashDispatch, HashSymbol, Num, Str, Symbol"
+    );
+}
+
+#[test]
+fn renders_lexical_error_span() {
+    let source = "#u8(1\n\t256)";
+    let err = crate::parse_ceceo(source).unwrap_err();
+    let rendered = render_parse_error("big.ceceo", source, &err);
+
+    assert_eq!(
+        rendered,
+        "error: number literal out of range
+ --> big.ceceo:2:2
+  |
+2 | \t256)
+  | \t^^^"
+    );
+}
+
+#[test]
+fn renders_unexpected_eof() {
+    let source = "(display (+ 1 2)";
+    let err = crate::parse_ceceo(source).unwrap_err();
+    let diagnostic = Diagnostic::from_parse_error(&err);
+
+    assert_eq!(diagnostic.message, "unexpected end of input");
+    assert_eq!(diagnostic.span, Span::new(16, 16));
+    assert!(diagnostic.expected.contains(&"\")\"".to_string()));
+    assert!(diagnostic
+        .render("eof.ceceo", source)
+        .contains(" --> eof.ceceo:1:17"));
+}
+
+#[test]
+fn renders_mismatched_bracket_locations() {
+    let source = "(cond\n  [(zero? x) 1)\n  [else 2])";
+    let err = crate::parse_ceceo(source).unwrap_err();
+    let rendered = render_parse_error("cond.ceceo", source, &err);
+
+    assert_eq!(
+        rendered,
+        "error: mismatched brackets: `[` closed by `)` instead of `]`
+ --> cond.ceceo:2:15
+  |
+2 |   [(zero? x) 1)
+  |               ^
+  = note: `[` opened here at cond.ceceo:2:3"
+    );
+}
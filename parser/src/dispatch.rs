@@ -0,0 +1,158 @@
+use std::{collections::HashMap, fmt::Debug};
+
+use crate::{ast::Node, lexer::LexicalError, span::Span};
+
+/// Reads the datum after `#name` into the node it stands for, or explains
+/// why it can't.
+pub type Expander = dyn Fn(&Node) -> Result<Node, String> + Send + Sync;
+
+/// Custom `#name` literals, so an application can add syntax like
+/// `#date"2024-01-01"` or `#rx(a+ b)` without changing the grammar.
+///
+/// A literal is `#name` followed directly, without whitespace, by a string
+/// or a list. The datum is parsed as usual and handed to the expander
+/// registered for `name`, and the literal reads as the node it returns,
+/// for instance a call that builds the value at run time. Names of the
+/// built-in literals, like `t` or `u8`, can't be taken over.
+#[derive(Default)]
+pub struct DispatchTable {
+    expanders: HashMap<String, Box<Expander>>,
+}
+
+impl DispatchTable {
+    /// A table without custom literals
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the `#name` literal, replacing the one registered before under
+    /// the same name.
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        expander: impl Fn(&Node) -> Result<Node, String> + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.expanders.insert(name.into(), Box::new(expander));
+        return self;
+    }
+
+    #[must_use]
+    pub fn contains(&self, name: &str) -> bool {
+        self.expanders.contains_key(name)
+    }
+
+    /// Expands the literal spanning `span`: `tag` is its `#name` and `datum`
+    /// what follows it.
+    pub(crate) fn expand(&self, tag: &str, datum: &Node, span: Span) -> Result<Node, LexicalError> {
+        let name = tag.strip_prefix('#').unwrap_or(tag);
+        let Some(expander) = self.expanders.get(name) else {
+            return Err(LexicalError::UnknownHashLiteral {
+                span: Span::new(span.start, span.start + tag.len()),
+            });
+        };
+
+        expander(datum).map_err(|message| LexicalError::InvalidLiteral { span, message })
+    }
+}
+
+impl Debug for DispatchTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.expanders.keys()).finish()
+    }
+}
+
+#[cfg(test)]
+fn date_table() -> DispatchTable {
+    use crate::ast::Atom;
+
+    let mut table = DispatchTable::new();
+    table
+        .register("date", |datum| {
+            let Node::Atom(Atom::Str(date)) = datum else {
+                return Err("expected a string".to_string());
+            };
+            let parts: Result<Vec<Node>, _> = date
+                .split('-')
+                .map(|part| part.parse::<u32>().map(|n| Node::Atom(Atom::Num(n.into()))))
+                .collect();
+            let mut call = vec![Node::Atom(Atom::Symbol("make-date".to_string()))];
+            call.extend(parts.map_err(|err| err.to_string())?);
+            Ok(Node::List(call))
+        })
+        .register("rx", |datum| Ok(Node::QuoteList(vec![datum.clone()])));
+
+    return table;
+}
+
+#[test]
+fn dispatch_expands_registered_literals() {
+    use crate::parse_ceceo_with;
+
+    let table = date_table();
+    let program = parse_ceceo_with("(f #date\"2024-01-31\" #rx[a (b c)])", &table).unwrap();
+    assert_eq!(
+        program[0].to_string(),
+        "(f (make-date 2024 1 31) '((a (b c))))"
+    );
+
+    assert_eq!(
+        crate::parse_ceceo_spanned_with("x #rx(y)", &table).unwrap()[1].span,
+        Span::new(2, 8)
+    );
+
+    // Quoting an expansion quotes the datum it stands for
+    let program = parse_ceceo_with("'#date\"2024-01-31\" '#rx(y)", &table).unwrap();
+    assert_eq!(program[0].to_string(), "'(make-date 2024 1 31)");
+    assert_eq!(program[1].to_string(), "'(quote ((y)))");
+}
+
+#[test]
+fn dispatch_reports_unknown_and_invalid_literals() {
+    use crate::{parse_ceceo, parse_ceceo_with};
+    use lalrpop_util::ParseError;
+
+    let table = date_table();
+    assert_eq!(
+        parse_ceceo_with("(a #time\"12:00\")", &table),
+        Err(ParseError::User {
+            error: LexicalError::UnknownHashLiteral {
+                span: Span::new(3, 8)
+            }
+        })
+    );
+    // Custom literals are only read with a table that has them
+    assert!(parse_ceceo("#date\"2024-01-31\"").is_err());
+
+    let err = parse_ceceo_with("#date(2024)", &table).unwrap_err();
+    assert_eq!(
+        err,
+        ParseError::User {
+            error: LexicalError::InvalidLiteral {
+                span: Span::new(0, 11),
+                message: "expected a string".to_string()
+            }
+        }
+    );
+    assert_eq!(err.to_string(), "invalid literal: expected a string");
+}
+
+#[test]
+fn cst_keeps_custom_literals() {
+    use crate::{cst, format::format_cst, parse_ceceo_with};
+
+    let table = date_table();
+    let source = "(list  #date\"2024-01-31\" #rx{a  b})";
+    let cst = cst::parse_with(source, &table).unwrap();
+    assert_eq!(cst.to_string(), source);
+    assert_eq!(cst.to_nodes(), parse_ceceo_with(source, &table).unwrap());
+    assert_eq!(
+        format_cst(&cst, 80),
+        "(list #date\"2024-01-31\" #rx{a b})\n"
+    );
+
+    assert_eq!(
+        cst::parse(source).unwrap_err(),
+        crate::parse_ceceo(source).unwrap_err()
+    );
+}
@@ -0,0 +1,174 @@
+use std::{fmt::Write, iter::Peekable, str::CharIndices};
+
+use crate::{lexer::LexicalError, span::Span};
+
+/// Decodes the escape sequences in the contents of a string literal.
+/// `offset` is the position of `raw` in the source, so errors point at the
+/// offending escape.
+///
+/// Supported escapes are `\"`, `\\`, `\n`, `\t`, `\r`, `\0`, `\a`, `\b`,
+/// `\x41;` and `\u{41}`. A backslash at the end of a line joins it with the
+/// next one, skipping the indentation.
+pub fn unescape_str(raw: &str, offset: usize) -> Result<String, LexicalError> {
+    let mut out = String::with_capacity(raw.len());
+    let mut chars = raw.char_indices().peekable();
+
+    while let Some((idx, c)) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+
+        let invalid_until = |end: usize| LexicalError::InvalidEscape {
+            span: Span::new(offset + idx, offset + end),
+        };
+        let Some((esc_idx, esc)) = chars.next() else {
+            return Err(invalid_until(raw.len()));
+        };
+
+        match esc {
+            '"' => out.push('"'),
+            '\\' => out.push('\\'),
+            'n' => out.push('\n'),
+            't' => out.push('\t'),
+            'r' => out.push('\r'),
+            '0' => out.push('\0'),
+            'a' => out.push('\u{7}'),
+            'b' => out.push('\u{8}'),
+            'x' => {
+                let (digits, end) =
+                    take_until(&mut chars, raw, ';').ok_or_else(|| invalid_until(esc_idx + 1))?;
+                out.push(char_from_hex(digits).ok_or_else(|| invalid_until(end))?);
+            }
+            'u' => {
+                if chars.next_if(|(_, c)| *c == '{').is_none() {
+                    return Err(invalid_until(esc_idx + 1));
+                }
+                let (digits, end) =
+                    take_until(&mut chars, raw, '}').ok_or_else(|| invalid_until(esc_idx + 2))?;
+                out.push(char_from_hex(digits).ok_or_else(|| invalid_until(end))?);
+            }
+            ' ' | '\t' | '\r' | '\n' => {
+                // Line continuation: only whitespace may sit between the backslash and the newline
+                let mut saw_newline = esc == '\n';
+                while let Some((ws_idx, ws)) =
+                    chars.next_if(|(_, c)| matches!(c, ' ' | '\t' | '\r' | '\n'))
+                {
+                    if ws == '\n' {
+                        if saw_newline {
+                            return Err(invalid_until(ws_idx));
+                        }
+                        saw_newline = true;
+                    }
+                }
+                if !saw_newline {
+                    return Err(invalid_until(esc_idx + 1));
+                }
+            }
+            other => return Err(invalid_until(esc_idx + other.len_utf8())),
+        }
+    }
+
+    return Ok(out);
+}
+
+/// Re-encodes `s` so it can be written back between double quotes.
+#[must_use]
+pub fn escape_str(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c if c.is_control() => _ = write!(out, "\\x{:X};", c as u32),
+            c => out.push(c),
+        }
+    }
+
+    return out;
+}
+
+/// Consumes chars up to and including `terminator`, returning the text before
+/// it and the index just past it.
+fn take_until<'a>(
+    chars: &mut Peekable<CharIndices<'a>>,
+    raw: &'a str,
+    terminator: char,
+) -> Option<(&'a str, usize)> {
+    let start = chars.peek()?.0;
+    for (idx, c) in chars.by_ref() {
+        if c == terminator {
+            return Some((&raw[start..idx], idx + 1));
+        }
+        if !c.is_ascii_hexdigit() {
+            return None;
+        }
+    }
+
+    return None;
+}
+
+fn char_from_hex(digits: &str) -> Option<char> {
+    let code = u32::from_str_radix(digits, 16).ok()?;
+    char::from_u32(code)
+}
+
+#[test]
+fn unescape_works() {
+    let raw = r#"say \"hi\"\\\n\tA=\x41; \u{3BB}"#;
+    assert_eq!(unescape_str(raw, 0).unwrap(), "say \"hi\"\\\n\tA=A λ");
+}
+
+#[test]
+fn unescape_joins_continued_lines() {
+    let raw = "one \\\n     two \\  \n\tthree";
+    assert_eq!(unescape_str(raw, 0).unwrap(), "one two three");
+}
+
+#[test]
+fn unescape_rejects_invalid_escapes() {
+    let invalid = |raw: &str| unescape_str(raw, 10).unwrap_err();
+
+    assert_eq!(
+        invalid(r"bad \q"),
+        LexicalError::InvalidEscape {
+            span: Span::new(14, 16)
+        }
+    );
+    assert_eq!(
+        invalid(r"\x41"),
+        LexicalError::InvalidEscape {
+            span: Span::new(10, 12)
+        }
+    );
+    assert_eq!(
+        invalid(r"\xD800;"),
+        LexicalError::InvalidEscape {
+            span: Span::new(10, 17)
+        }
+    );
+    assert_eq!(
+        invalid(r"\u41"),
+        LexicalError::InvalidEscape {
+            span: Span::new(10, 12)
+        }
+    );
+    assert_eq!(
+        invalid("\\ x"),
+        LexicalError::InvalidEscape {
+            span: Span::new(10, 12)
+        }
+    );
+}
+
+#[test]
+fn escape_round_trips() {
+    let original = "tab\tquote\" slash\\ newline\n bell\u{7} λ";
+    let escaped = escape_str(original);
+
+    assert_eq!(escaped, "tab\\tquote\\\" slash\\\\ newline\\n bell\\x7; λ");
+    assert_eq!(unescape_str(&escaped, 0).unwrap(), original);
+}
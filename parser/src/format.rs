@@ -0,0 +1,470 @@
+use crate::{
+    ast::{Atom, Bracket},
+    cst::{self, Cst, CstDot, CstKind, CstNode, Trivia, TriviaKind},
+    CeceoParseError,
+};
+
+pub const DEFAULT_WIDTH: usize = 80;
+
+/// Rewrites `input` in the canonical style, keeping its comments and the
+/// kind of every bracket.
+pub fn format_source(input: &str) -> Result<String, CeceoParseError<'_>> {
+    let cst = cst::parse(input)?;
+
+    Ok(format_cst(&cst, DEFAULT_WIDTH))
+}
+
+/// Prints a tree in the canonical style, in lines of at most `width` columns
+/// where possible.
+///
+/// A form is kept on one line when it fits and has no line comments inside.
+/// Otherwise the body
+/// of special forms like `define` is indented by two spaces, the arguments
+/// of a call are aligned under the first one, and the items of other lists
+/// are aligned under the first item.
+///
+/// Blank lines between forms are kept, but runs of them become one.
+#[must_use]
+pub fn format_cst(cst: &Cst<'_>, width: usize) -> String {
+    let mut printer = Printer {
+        width,
+        quoted: false,
+        out: String::new(),
+    };
+
+    for node in &cst.nodes {
+        printer.node_on_new_line(node, 0, 0);
+    }
+    printer.comments(&cst.trailing, 0);
+    if !printer.out.is_empty() {
+        printer.trim_line_end();
+        printer.out.push('\n');
+    }
+
+    return printer.out;
+}
+
+struct Printer {
+    width: usize,
+    /// Inside a quote lists are data, even when they start with a symbol
+    quoted: bool,
+    out: String,
+}
+
+/// What the comments before a datum leave behind them
+struct Gap {
+    /// Whether any comment was written
+    comment: bool,
+    /// A line comment was written, so the datum must go on the next line
+    line_break: bool,
+    /// The datum was separated from the comments or the previous datum by
+    /// a blank line
+    blank_line: bool,
+}
+
+/// How the items of a list that doesn't fit on one line are laid out
+enum Layout {
+    /// A special form: the head and this many arguments go on the first
+    /// line, the body below, indented by two spaces
+    Body(usize),
+    /// A call: the head and the first argument go on the first line, the
+    /// other arguments below, aligned under the first one
+    Call,
+    /// Every item below the first one and aligned under it
+    Data,
+}
+
+impl Printer {
+    fn column(&self) -> usize {
+        let line_start = self.out.rfind('\n').map_or(0, |i| i + 1);
+        self.out[line_start..].chars().count()
+    }
+
+    fn trim_line_end(&mut self) {
+        let len = self.out.trim_end_matches(' ').len();
+        self.out.truncate(len);
+    }
+
+    fn line_break(&mut self, indent: usize, blank_line: bool) {
+        self.trim_line_end();
+        self.out.push('\n');
+        if blank_line {
+            self.out.push('\n');
+        }
+        self.out.extend(std::iter::repeat_n(' ', indent));
+    }
+
+    /// Writes the comments in `trivia`, dropping the whitespace around
+    /// them. A comment that followed the previous token on the same line
+    /// stays there; the others go on lines of their own at `indent`.
+    fn comments(&mut self, trivia: &[Trivia<'_>], indent: usize) -> Gap {
+        let mut gap = Gap {
+            comment: false,
+            line_break: false,
+            blank_line: false,
+        };
+        let mut newlines = 0;
+        for t in trivia {
+            if t.kind == TriviaKind::Whitespace {
+                newlines += t.text.matches('\n').count();
+                continue;
+            }
+
+            if self.out.is_empty() {
+                // Nothing to separate the first comment of the file from
+            } else if newlines == 0 && !gap.line_break {
+                if !self.out.ends_with(['(', '[', '{']) {
+                    self.out.push(' ');
+                }
+            } else {
+                self.line_break(indent, newlines > 1);
+            }
+            self.out.push_str(t.text.trim_end());
+            gap.comment = true;
+            gap.line_break = t.kind == TriviaKind::LineComment;
+            newlines = 0;
+        }
+        gap.blank_line = newlines > 1;
+
+        return gap;
+    }
+
+    /// Writes `node` on a line of its own, starting at column `indent`.
+    /// `tail` is the number of closing brackets that will follow it.
+    fn node_on_new_line(&mut self, node: &CstNode<'_>, indent: usize, tail: usize) {
+        let gap = self.comments(&node.leading, indent);
+        if !self.out.is_empty() {
+            self.line_break(indent, gap.blank_line);
+        }
+        self.datum(node, tail);
+    }
+
+    /// Writes `node` on the same line as what comes before it, unless a
+    /// line comment is in the way, in which case it starts the next line at
+    /// `indent`.
+    fn node_after(&mut self, node: &CstNode<'_>, indent: usize, space: bool, tail: usize) {
+        let gap = self.comments(&node.leading, indent);
+        if gap.line_break {
+            self.line_break(indent, false);
+        } else if space || gap.comment {
+            self.out.push(' ');
+        }
+        self.datum(node, tail);
+    }
+
+    /// Writes a node at the current column, without its leading trivia
+    fn datum(&mut self, node: &CstNode<'_>, tail: usize) {
+        if let Some(flat) = flat(node) {
+            let fits = self.column() + flat.chars().count() + tail <= self.width;
+            if fits || matches!(node.kind, CstKind::Atom { .. }) {
+                self.out.push_str(&flat);
+                return;
+            }
+        }
+
+        match &node.kind {
+            CstKind::Atom { text, .. } => self.out.push_str(text),
+            CstKind::List {
+                bracket,
+                items,
+                dot,
+                trailing,
+            } => {
+                let layout = if self.quoted {
+                    Layout::Data
+                } else {
+                    layout(items)
+                };
+                self.list(*bracket, items, dot.as_ref(), trailing, &layout, tail);
+            }
+            CstKind::Vector {
+                bytes,
+                bracket,
+                items,
+                trailing,
+            } => {
+                self.out.push_str(if *bytes { "#u8" } else { "#" });
+                self.list(*bracket, items, None, trailing, &Layout::Data, tail);
+            }
+            CstKind::Quote(node) => self.prefixed("'", true, node, tail),
+            CstKind::Quasiquote(node) => self.prefixed("`", true, node, tail),
+            CstKind::Unquote(node) => self.prefixed(",", false, node, tail),
+            CstKind::UnquoteSplicing(node) => self.prefixed(",@", false, node, tail),
+            CstKind::Dispatch { tag, datum, .. } => self.prefixed(tag, true, datum, tail),
+        }
+    }
+
+    fn prefixed(&mut self, prefix: &str, quoted: bool, node: &CstNode<'_>, tail: usize) {
+        self.out.push_str(prefix);
+        self.out.push_str(prefix_gap(prefix, node));
+        let indent = self.column();
+        let outer = std::mem::replace(&mut self.quoted, quoted);
+        self.node_after(node, indent, false, tail);
+        self.quoted = outer;
+    }
+
+    fn list(
+        &mut self,
+        bracket: Bracket,
+        items: &[CstNode<'_>],
+        dot: Option<&CstDot<'_>>,
+        trailing: &[Trivia<'_>],
+        layout: &Layout,
+        tail: usize,
+    ) {
+        let open = self.column();
+        self.out.push(bracket.open_char());
+
+        let (first_line, mut indent) = match layout {
+            Layout::Body(args) => (args + 1, open + 2),
+            Layout::Call => (2, open + 1),
+            Layout::Data => (1, open + 1),
+        };
+        for (i, item) in items.iter().enumerate() {
+            let is_last = i + 1 == items.len() && dot.is_none();
+            let tail = if is_last { tail + 1 } else { 0 };
+            if i == 0 {
+                self.node_after(item, indent, false, tail);
+                // Arguments far to the right would leave little room
+                // for themselves, so they go under the head instead
+                if matches!(layout, Layout::Call) && self.column() < open + self.width / 2 {
+                    indent = self.column() + 1;
+                }
+            } else if i < first_line {
+                self.node_after(item, indent, true, tail);
+            } else {
+                self.node_on_new_line(item, indent, tail);
+            }
+        }
+
+        if let Some(dot) = dot {
+            let gap = self.comments(&dot.leading, indent);
+            self.line_break(indent, gap.blank_line);
+            self.out.push('.');
+            self.node_after(&dot.tail, indent + 2, true, tail + 1);
+        }
+
+        if self.comments(trailing, indent).line_break {
+            self.line_break(indent, false);
+        }
+        self.out.push(bracket.close_char());
+    }
+}
+
+fn symbol<'a>(node: Option<&'a CstNode<'_>>) -> Option<&'a str> {
+    match node.map(|node| &node.kind) {
+        Some(CstKind::Atom {
+            atom: Atom::Symbol(symbol),
+            ..
+        }) => Some(symbol),
+        _ => None,
+    }
+}
+
+fn layout(items: &[CstNode<'_>]) -> Layout {
+    let args = match symbol(items.first()) {
+        None => return Layout::Data,
+        Some("begin" | "cond") => 0,
+        // A named let has its name before the bindings
+        Some("let") if symbol(items.get(1)).is_some() => 2,
+        Some(
+            "define" | "define-syntax" | "lambda" | "let" | "let*" | "letrec" | "letrec*"
+            | "let-values" | "case" | "when" | "unless" | "syntax-rules",
+        ) => 1,
+        Some("do") => 2,
+        Some(_) => return Layout::Call,
+    };
+
+    return Layout::Body(args);
+}
+
+/// `node` printed on a single line, unless it has a comment that must end
+/// its line inside or an atom spanning several lines
+fn flat(node: &CstNode<'_>) -> Option<String> {
+    match &node.kind {
+        CstKind::Atom { text, .. } => (!text.contains('\n')).then(|| (*text).to_string()),
+        CstKind::List {
+            bracket,
+            items,
+            dot,
+            trailing,
+        } => {
+            let mut parts = Vec::new();
+            for item in items {
+                flat_item(&mut parts, item)?;
+            }
+            if let Some(dot) = dot {
+                parts.extend(inline_comments(&dot.leading)?);
+                parts.push(".".to_string());
+                flat_item(&mut parts, &dot.tail)?;
+            }
+            parts.extend(inline_comments(trailing)?);
+            Some(format!(
+                "{}{}{}",
+                bracket.open_char(),
+                parts.join(" "),
+                bracket.close_char()
+            ))
+        }
+        CstKind::Vector {
+            bytes,
+            bracket,
+            items,
+            trailing,
+        } => {
+            let mut parts = Vec::new();
+            for item in items {
+                flat_item(&mut parts, item)?;
+            }
+            parts.extend(inline_comments(trailing)?);
+            Some(format!(
+                "{}{}{}{}",
+                if *bytes { "#u8" } else { "#" },
+                bracket.open_char(),
+                parts.join(" "),
+                bracket.close_char()
+            ))
+        }
+        CstKind::Quote(node) => flat_prefixed("'", node),
+        CstKind::Quasiquote(node) => flat_prefixed("`", node),
+        CstKind::Unquote(node) => flat_prefixed(",", node),
+        CstKind::UnquoteSplicing(node) => flat_prefixed(",@", node),
+        CstKind::Dispatch { tag, datum, .. } => flat_prefixed(tag, datum),
+    }
+}
+
+fn flat_item(parts: &mut Vec<String>, item: &CstNode<'_>) -> Option<()> {
+    parts.extend(inline_comments(&item.leading)?);
+    parts.push(flat(item)?);
+
+    Some(())
+}
+
+fn flat_prefixed(prefix: &str, node: &CstNode<'_>) -> Option<String> {
+    if !inline_comments(&node.leading)?.is_empty() {
+        return None;
+    }
+
+    flat(node).map(|flat| format!("{prefix}{}{flat}", prefix_gap(prefix, node)))
+}
+
+/// What goes between a prefix and its datum: nothing, except for an unquoted
+/// symbol starting with `@`, as `,@x` would be an unquote-splicing
+fn prefix_gap(prefix: &str, node: &CstNode<'_>) -> &'static str {
+    let starts_with_at = matches!(node.kind, CstKind::Atom { text, .. } if text.starts_with('@'));
+    if prefix == "," && starts_with_at {
+        return " ";
+    }
+
+    return "";
+}
+
+/// The comments in `trivia`, unless one of them is on a line of its own or
+/// ends its line
+fn inline_comments(trivia: &[Trivia<'_>]) -> Option<Vec<String>> {
+    let mut after_newline = false;
+    let mut comments = Vec::new();
+    for t in trivia {
+        match t.kind {
+            TriviaKind::Whitespace => after_newline |= t.text.contains('\n'),
+            TriviaKind::LineComment => return None,
+            TriviaKind::BlockComment | TriviaKind::DatumComment => {
+                if after_newline || t.text.contains('\n') {
+                    return None;
+                }
+                comments.push(t.text.to_string());
+            }
+        }
+    }
+
+    Some(comments)
+}
+
+#[cfg(test)]
+fn format_with_width(input: &str, width: usize) -> String {
+    format_cst(&cst::parse(input).unwrap(), width)
+}
+
+#[test]
+fn formats_short_forms_on_one_line() {
+    assert_eq!(
+        format_source("(define   x\n 1)\n\n\n\n( display  [+ x {* 2 3}] )").unwrap(),
+        "(define x 1)\n\n(display [+ x {* 2 3}])\n"
+    );
+    assert_eq!(
+        format_source("  '( a . b ) `(1 ,x ,@ y) #( 1 2 ) #u8[ 0 255 ]").unwrap(),
+        "'(a . b)\n`(1 ,x ,@y)\n#(1 2)\n#u8[0 255]\n"
+    );
+    assert_eq!(format_source("`(a ,  @x ,@ y)").unwrap(), "`(a , @x ,@y)\n");
+    assert_eq!(format_source("").unwrap(), "");
+    assert_eq!(format_source(" \n\n ").unwrap(), "");
+}
+
+#[test]
+fn indents_special_forms_by_body() {
+    let source = "(define (fact n) (cond [(= n 0) 1] [else (* n (fact (- n 1)))]))";
+    assert_eq!(
+        format_with_width(source, 40),
+        "(define (fact n)\n  (cond\n    [(= n 0) 1]\n    [else (* n (fact (- n 1)))]))\n"
+    );
+    assert_eq!(
+        format_with_width("(define f (lambda (x y) (display x) (+ x y)))", 30),
+        "(define f\n  (lambda (x y)\n    (display x)\n    (+ x y)))\n"
+    );
+}
+
+#[test]
+fn aligns_arguments_and_data() {
+    assert_eq!(
+        format_with_width("(list (+ 1 2) (* 3 4) '(a b c d))", 20),
+        "(list (+ 1 2)\n      (* 3 4)\n      '(a b c d))\n"
+    );
+    assert_eq!(
+        format_with_width("'((one 1) (two 2) (three 3))", 20),
+        "'((one 1)\n  (two 2)\n  (three 3))\n"
+    );
+    assert_eq!(
+        format_with_width("'(alpha beta gamma . delta)", 10),
+        "'(alpha\n  beta\n  gamma\n  . delta)\n"
+    );
+}
+
+#[test]
+fn keeps_comments() {
+    let source = "; header\n\n\n(define (f x) ; trailing\n  [* x #| inline |# 2])\n#;(ignored form)\n(f 21) ; done";
+    assert_eq!(
+        format_source(source).unwrap(),
+        "; header\n\n(define (f x) ; trailing\n  [* x #| inline |# 2])\n#;(ignored form)\n(f 21) ; done\n"
+    );
+    assert_eq!(
+        format_source("(a b ; last\n)").unwrap(),
+        "(a b ; last\n   )\n"
+    );
+}
+
+#[test]
+fn formatting_is_idempotent_and_keeps_the_program() {
+    let sources = [
+        "(define (f x) ; trailing\n  [* x #| inline |# 2])\n\n(f 21)",
+        "(let loop ((i 0) (acc '())) (if (< i 10) (loop (+ i 1) (cons i acc)) acc))",
+        "`(a ,b ,@ (c d e f g h i j k l m n o p q r s t u v w x y z) ` ,'d) '(1 . ; tail\n (2))",
+        "(when (> x 0) (display \"a long string that pushes past the width\") (newline) x)",
+        "(a\n;c\n b . c)",
+        "`(a , @x ,@y)",
+    ];
+    for source in sources {
+        for width in [10, 40, 80] {
+            let formatted = format_with_width(source, width);
+            assert_eq!(
+                format_with_width(&formatted, width),
+                formatted,
+                "{source:?}"
+            );
+            assert_eq!(
+                crate::parse_ceceo(&formatted).unwrap(),
+                crate::parse_ceceo(source).unwrap(),
+                "{formatted:?}"
+            );
+        }
+    }
+}
@@ -1,4 +1,11 @@
-use std::{iter::Peekable, str::CharIndices};
+use std::{fmt::Display, iter::Peekable, str::CharIndices};
+
+use crate::{
+    ast::{parse_hash_symbol, Bracket},
+    escape::unescape_str,
+    number::{parse_number, LiteralError},
+    span::Span,
+};
 
 pub type Spanned<Tok, Loc, Error> = Result<(Loc, Tok, Loc), Error>;
 type LexerItem<'input> = Spanned<Tok<'input>, usize, LexicalError>;
@@ -6,23 +13,160 @@ type LexerItem<'input> = Spanned<Tok<'input>, usize, LexicalError>;
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Tok<'input> {
     Whitespace,
-    LeftParen,
-    RightParen,
+    LeftParen(Bracket),
+    RightParen(Bracket),
+    /// `#(`, opening a vector
+    VectorStart(Bracket),
+    /// `#u8(`, opening a bytevector
+    BytevectorStart(Bracket),
     Quote,
+    Dot,
+    Quasiquote,
+    Unquote,
+    UnquoteSplicing,
     Symbol(&'input str),
     Str(&'input str),
     Num(&'input str),
     HashSymbol(&'input str),
+    /// `#name` directly followed by a string or a list, a literal read by
+    /// the `name` entry of a [`DispatchTable`](crate::dispatch::DispatchTable)
+    HashDispatch(&'input str),
+    Comment(&'input str),
 }
 
-#[derive(Debug, PartialEq, Eq)]
+impl Tok<'_> {
+    /// Whether the token is one of the quote prefixes or a `#name` that
+    /// apply to the datum after them.
+    #[must_use]
+    pub const fn is_prefix(&self) -> bool {
+        matches!(
+            self,
+            Tok::Quote
+                | Tok::Quasiquote
+                | Tok::Unquote
+                | Tok::UnquoteSplicing
+                | Tok::HashDispatch(_)
+        )
+    }
+}
+
+impl Display for Tok<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Tok::Whitespace => write!(f, "whitespace"),
+            Tok::Comment(_) => write!(f, "comment"),
+            Tok::LeftParen(bracket) => write!(f, "{}", bracket.open_char()),
+            Tok::RightParen(bracket) => write!(f, "{}", bracket.close_char()),
+            Tok::VectorStart(bracket) => write!(f, "#{}", bracket.open_char()),
+            Tok::BytevectorStart(bracket) => write!(f, "#u8{}", bracket.open_char()),
+            Tok::Quote => write!(f, "'"),
+            Tok::Dot => write!(f, "."),
+            Tok::Quasiquote => write!(f, "`"),
+            Tok::Unquote => write!(f, ","),
+            Tok::UnquoteSplicing => write!(f, ",@"),
+            Tok::Str(s) => write!(f, "\"{s}\""),
+            Tok::Symbol(s) | Tok::Num(s) | Tok::HashSymbol(s) | Tok::HashDispatch(s) => {
+                write!(f, "{s}")
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum LexicalError {
-    // Not possible
+    /// A string literal that reaches the end of the input without its closing quote.
+    UnterminatedString { start: usize },
+    /// A backslash escape in a string literal that isn't recognized or is malformed.
+    InvalidEscape { span: Span },
+    /// A character that cannot start or continue any token.
+    InvalidCharacter { ch: char, pos: usize },
+    /// A token that starts like a number but contains non-digit characters.
+    MalformedNumber { span: Span },
+    /// A well-formed integer literal that does not fit in a number atom.
+    NumberOutOfRange { span: Span },
+    /// A `#` literal other than the known boolean spellings and characters.
+    UnknownHashLiteral { span: Span },
+    /// A `#\\` character literal with a name that isn't a known character.
+    UnknownCharName { span: Span },
+    /// A closing bracket of a different kind than the one it closes, like `(a]`.
+    MismatchedBracket {
+        open: Span,
+        open_kind: Bracket,
+        close: Span,
+        close_kind: Bracket,
+    },
+    /// A `#|` block comment that reaches the end of the input without its closing `|#`.
+    UnterminatedBlockComment { start: usize },
+    /// A `#;` datum comment that isn't followed by a datum to comment out.
+    /// When the input ends before the datum does, the span reaches the end.
+    MissingCommentedDatum { span: Span },
+    /// A custom `#name` literal its reader rejected, with the reason.
+    InvalidLiteral { span: Span, message: String },
 }
 
+impl LexicalError {
+    /// The region of the input the error refers to.
+    #[must_use]
+    pub const fn span(&self) -> Span {
+        match self {
+            Self::UnterminatedString { start } => Span::new(*start, *start + 1),
+            Self::UnterminatedBlockComment { start } => Span::new(*start, *start + 2),
+            Self::InvalidCharacter { ch, pos } => Span::new(*pos, *pos + ch.len_utf8()),
+            Self::InvalidEscape { span }
+            | Self::MalformedNumber { span }
+            | Self::NumberOutOfRange { span }
+            | Self::UnknownHashLiteral { span }
+            | Self::UnknownCharName { span }
+            | Self::MissingCommentedDatum { span }
+            | Self::InvalidLiteral { span, .. } => *span,
+            Self::MismatchedBracket { close, .. } => *close,
+        }
+    }
+}
+
+impl Display for LexicalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnterminatedString { .. } => write!(f, "unterminated string literal"),
+            Self::InvalidEscape { .. } => write!(f, "invalid escape sequence in string"),
+            Self::InvalidCharacter { ch, .. } => {
+                write!(f, "invalid character {ch:?}")
+            }
+            Self::MalformedNumber { .. } => write!(f, "malformed number literal"),
+            Self::NumberOutOfRange { .. } => write!(f, "number literal out of range"),
+            Self::UnknownHashLiteral { .. } => write!(f, "unknown hash literal"),
+            Self::UnknownCharName { .. } => write!(f, "unknown character name"),
+            Self::MismatchedBracket {
+                open_kind,
+                close_kind,
+                ..
+            } => write!(
+                f,
+                "mismatched brackets: `{}` closed by `{}` instead of `{}`",
+                open_kind.open_char(),
+                close_kind.close_char(),
+                open_kind.close_char()
+            ),
+            Self::UnterminatedBlockComment { .. } => write!(f, "unterminated block comment"),
+            Self::MissingCommentedDatum { .. } => {
+                write!(f, "datum comment is not followed by a datum")
+            }
+            Self::InvalidLiteral { message, .. } => write!(f, "invalid literal: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for LexicalError {}
+
 pub struct Lexer<'input> {
     chars: Peekable<CharIndices<'input>>,
     input: &'input str,
+    // Brackets that haven't been closed yet, with their positions
+    open_brackets: Vec<(Bracket, usize)>,
+    // Token held back while an error found before it is reported
+    pending: Option<LexerItem<'input>>,
+    // Whether whitespace and comments are produced as tokens instead of skipped
+    keep_trivia: bool,
 }
 
 impl<'input> Lexer<'input> {
@@ -31,6 +175,19 @@ impl<'input> Lexer<'input> {
         Lexer {
             chars: input.char_indices().peekable(),
             input,
+            open_brackets: Vec::new(),
+            pending: None,
+            keep_trivia: false,
+        }
+    }
+
+    /// A lexer that also produces `Whitespace` and `Comment` tokens, so the
+    /// token spans cover the whole input.
+    #[must_use]
+    pub fn with_trivia(input: &'input str) -> Self {
+        Lexer {
+            keep_trivia: true,
+            ..Lexer::new(input)
         }
     }
 
@@ -44,18 +201,24 @@ impl<'input> Lexer<'input> {
         condition: impl Fn(char) -> bool,
         tok_type: Tok,
     ) -> Option<LexerItem<'input>> {
-        let mut end_idx = start_idx + 1;
-
         let output_lexer_item = |new_end_idx: usize| -> Option<LexerItem<'input>> {
             let tok = match tok_type {
                 Tok::Symbol(_) => Tok::Symbol(&self.input[start_idx..new_end_idx]),
                 Tok::Str(_) => Tok::Str(&self.input[start_idx..new_end_idx]),
                 Tok::Num(_) => Tok::Num(&self.input[start_idx..new_end_idx]),
                 Tok::HashSymbol(_) => Tok::HashSymbol(&self.input[start_idx..new_end_idx]),
+                Tok::HashDispatch(_) => Tok::HashDispatch(&self.input[start_idx..new_end_idx]),
+                Tok::Comment(_) => Tok::Comment(&self.input[start_idx..new_end_idx]),
                 Tok::Whitespace => Tok::Whitespace,
-                Tok::LeftParen => Tok::LeftParen,
-                Tok::RightParen => Tok::RightParen,
+                Tok::LeftParen(bracket) => Tok::LeftParen(bracket),
+                Tok::RightParen(bracket) => Tok::RightParen(bracket),
+                Tok::VectorStart(bracket) => Tok::VectorStart(bracket),
+                Tok::BytevectorStart(bracket) => Tok::BytevectorStart(bracket),
                 Tok::Quote => Tok::Quote,
+                Tok::Dot => Tok::Dot,
+                Tok::Quasiquote => Tok::Quasiquote,
+                Tok::Unquote => Tok::Unquote,
+                Tok::UnquoteSplicing => Tok::UnquoteSplicing,
             };
 
             return Some(Ok((start_idx, tok, new_end_idx)));
@@ -65,17 +228,14 @@ impl<'input> Lexer<'input> {
             match self.chars.peek() {
                 // Condition fulfilled
                 Some((idx, c)) if !condition(*c) => {
-                    end_idx = *idx;
-                    return output_lexer_item(end_idx);
+                    return output_lexer_item(*idx);
                 }
                 // String end
                 None => {
-                    end_idx += 1;
-                    return output_lexer_item(end_idx);
+                    return output_lexer_item(self.input.len());
                 }
                 // Skip char
-                Some((idx, _)) => {
-                    end_idx = *idx;
+                Some(_) => {
                     self.consume()?;
                 }
             }
@@ -87,50 +247,182 @@ impl<'input> Lexer<'input> {
     }
 
     fn consume_symbol(&mut self, start_idx: usize) -> Option<LexerItem<'input>> {
-        self.consume_while(start_idx, Lexer::is_symbol_char, Tok::Symbol(""))
+        match self.consume_while(start_idx, Lexer::is_symbol_char, Tok::Symbol(""))? {
+            // A lone `.` separates the tail of an improper list, `...` is still a symbol
+            Ok((start, Tok::Symbol("."), end)) => Some(Ok((start, Tok::Dot, end))),
+            // Numbers like `+5` and `.5` don't start with a digit
+            Ok((start, Tok::Symbol(s), end)) if Lexer::is_number_literal(s) => {
+                Some(Ok((start, Tok::Num(s), end)))
+            }
+            item => Some(item),
+        }
     }
 
     fn consume_hash_symbol(&mut self, start_idx: usize) -> Option<LexerItem<'input>> {
-        self.consume_while(start_idx, Lexer::is_symbol_char, Tok::HashSymbol(""))
-    }
+        let after_hash = &self.input[start_idx + 1..];
+        let open_bracket = |s: &str| s.chars().next().and_then(Bracket::from_open_char);
+        if let Some(bracket) = open_bracket(after_hash) {
+            return self.consume_vector_start(start_idx, "#", Tok::VectorStart(bracket));
+        }
+        if let Some(bracket) = after_hash.strip_prefix("u8").and_then(open_bracket) {
+            return self.consume_vector_start(start_idx, "#u8", Tok::BytevectorStart(bracket));
+        }
 
-    fn consume_symbol_or_neg_num(&mut self, start_idx: usize) -> Option<LexerItem<'input>> {
-        let result = self.consume_while(start_idx, Lexer::is_symbol_char, Tok::Symbol(""));
-        match result {
-            Some(tok_result) => match tok_result {
-                Ok(tok_tuple) => {
-                    let tok = tok_tuple.1;
-                    if let Tok::Symbol(s) | Tok::Num(s) = tok {
-                        let is_numeric = s.parse::<i32>().is_ok();
-                        if is_numeric {
-                            return Some(Ok((tok_tuple.0, Tok::Num(s), tok_tuple.2)));
-                        }
-
-                        return Some(tok_result);
-                    }
+        if self.input[start_idx + 1..].starts_with('\\') {
+            _ = self.consume(); // consume #
+            _ = self.consume(); // consume \
+
+            // The first char is taken as is, so `#\(` and `#\ ` are characters too
+            if self.consume().is_none() {
+                return Some(Err(LexicalError::UnknownCharName {
+                    span: Span::new(start_idx, self.input.len()),
+                }));
+            }
+        }
 
-                    return None;
+        let result = self.consume_while(start_idx, Lexer::is_symbol_char, Tok::HashSymbol(""))?;
+        return Some(result.and_then(|(start, tok, end)| {
+            let text = &self.input[start..end];
+            // `#x1F`, `#b101`, `#e1.5`: a number with a radix or exactness prefix
+            if Lexer::is_number_literal(text) {
+                return Ok((start, Tok::Num(text), end));
+            }
+            if Lexer::is_number_prefix(text) {
+                return Err(LexicalError::MalformedNumber {
+                    span: Span::new(start, end),
+                });
+            }
+
+            match parse_hash_symbol(text, Span::new(start, end)) {
+                Ok(_) => Ok((start, tok, end)),
+                // `#name"..."` or `#name(...)`, left for the parser to look up.
+                // A bare `#` has no name to look up.
+                Err(LexicalError::UnknownHashLiteral { .. })
+                    if text.len() > 1 && self.input[end..].starts_with(['"', '(', '[', '{']) =>
+                {
+                    Ok((start, Tok::HashDispatch(text), end))
                 }
-                Err(_) => return None,
-            },
-            None => return None,
+                Err(err) => Err(err),
+            }
+        }));
+    }
+
+    fn consume_symbol_or_neg_num(&mut self, start_idx: usize) -> Option<LexerItem<'input>> {
+        // `-` followed by a digit always starts a number, anything else is a symbol
+        let starts_number = self.input[start_idx + 1..].starts_with(Lexer::is_decimal_digit);
+        if starts_number {
+            return self.consume_num(start_idx);
         }
+
+        self.consume_symbol(start_idx)
     }
 
     fn consume_num(&mut self, start_idx: usize) -> Option<LexerItem<'input>> {
-        self.consume_while(start_idx, Lexer::is_decimal_digit, Tok::Num(""))
+        let result = self.consume_while(start_idx, Lexer::is_symbol_char, Tok::Num(""))?;
+        return Some(result.and_then(|(start, tok, end)| match tok {
+            Tok::Num(s) if Lexer::is_number_literal(s) => Ok((start, tok, end)),
+            _ => Err(LexicalError::MalformedNumber {
+                span: Span::new(start, end),
+            }),
+        }));
     }
 
     fn consume_string(&mut self, start_idx: usize) -> Option<LexerItem<'input>> {
         _ = self.consume()?; // consume leading "
-        let res = self.consume_while(
-            start_idx + 1,
-            |c: char| -> bool { !Lexer::is_string_quote(c) },
-            Tok::Str(""),
-        );
-        _ = self.consume()?; // consume trailing "
+        let content_start = start_idx + 1;
+
+        // The token holds the raw contents, escapes are decoded by the parser
+        while let Some((idx, c)) = self.consume() {
+            if Lexer::is_string_quote(c) {
+                let raw = &self.input[content_start..idx];
+                if let Err(err) = unescape_str(raw, content_start) {
+                    return Some(Err(err));
+                }
+
+                // The span covers both quotes
+                return Some(Ok((start_idx, Tok::Str(raw), idx + 1)));
+            }
+
+            if Lexer::is_escape_char(c) {
+                _ = self.consume();
+            }
+        }
+
+        return Some(Err(LexicalError::UnterminatedString { start: start_idx }));
+    }
+
+    fn consume_line_comment(&mut self, start_idx: usize) -> Option<LexerItem<'input>> {
+        self.consume_while(start_idx, |c: char| c != '\n', Tok::Comment(""))
+    }
+
+    #[allow(clippy::unnecessary_wraps)]
+    fn consume_block_comment(&mut self, start_idx: usize) -> Option<LexerItem<'input>> {
+        // Block comments nest, so `#| a #| b |# c |#` is a single comment
+        let mut depth = 0_usize;
+        while let Some((idx, c)) = self.consume() {
+            let next = self.chars.peek().map(|(_, next)| *next);
+            match (c, next) {
+                ('#', Some('|')) => {
+                    _ = self.consume();
+                    depth += 1;
+                }
+                ('|', Some('#')) => {
+                    _ = self.consume();
+                    depth -= 1;
+                    if depth == 0 {
+                        let end_idx = idx + 2;
+                        let tok = Tok::Comment(&self.input[start_idx..end_idx]);
+                        return Some(Ok((start_idx, tok, end_idx)));
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        return Some(Err(LexicalError::UnterminatedBlockComment {
+            start: start_idx,
+        }));
+    }
+
+    #[allow(clippy::unnecessary_wraps)]
+    fn consume_datum_comment(&mut self, start_idx: usize) -> Option<LexerItem<'input>> {
+        let missing_datum = LexicalError::MissingCommentedDatum {
+            span: Span::new(start_idx, start_idx + 2),
+        };
+        _ = self.consume(); // consume #
+        _ = self.consume(); // consume ;
+
+        // Lex the commented datum as usual and throw its tokens away
+        let mut depth = 0_usize;
+        loop {
+            let item = match self.next() {
+                Some(Ok(item)) => item,
+                Some(Err(err)) => return Some(Err(err)),
+                None => {
+                    return Some(Err(LexicalError::MissingCommentedDatum {
+                        span: Span::new(start_idx, self.input.len()),
+                    }))
+                }
+            };
+            let (_, tok, end_idx) = item;
 
-        return res;
+            match tok {
+                Tok::Whitespace | Tok::Comment(_) => continue,
+                Tok::LeftParen(_) | Tok::VectorStart(_) | Tok::BytevectorStart(_) => depth += 1,
+                // The closer or dot is left for the enclosing list
+                Tok::RightParen(_) | Tok::Dot if depth == 0 => {
+                    self.pending = Some(Ok(item));
+                    return Some(Err(missing_datum));
+                }
+                Tok::RightParen(_) => depth -= 1,
+                _ => (),
+            }
+
+            if depth == 0 && !tok.is_prefix() {
+                let tok = Tok::Comment(&self.input[start_idx..end_idx]);
+                return Some(Ok((start_idx, tok, end_idx)));
+            }
+        }
     }
 
     #[allow(clippy::unnecessary_wraps)]
@@ -143,28 +435,100 @@ impl<'input> Lexer<'input> {
         return Some(Ok((start_idx, tok, start_idx + 1)));
     }
 
-    fn consume_left_paren(&mut self, start_idx: usize) -> Option<LexerItem<'input>> {
-        self.consume_single_char(start_idx, Tok::LeftParen)
+    fn consume_left_paren(&mut self, start_idx: usize, ch: char) -> Option<LexerItem<'input>> {
+        let bracket = Bracket::from_open_char(ch)?;
+        self.open_brackets.push((bracket, start_idx));
+        self.consume_single_char(start_idx, Tok::LeftParen(bracket))
     }
 
-    fn consume_right_paren(&mut self, start_idx: usize) -> Option<LexerItem<'input>> {
-        self.consume_single_char(start_idx, Tok::RightParen)
+    /// Consumes a `prefix` followed by an opening bracket, like `#(`.
+    #[allow(clippy::unnecessary_wraps)]
+    fn consume_vector_start(
+        &mut self,
+        start_idx: usize,
+        prefix: &str,
+        tok: Tok<'input>,
+    ) -> Option<LexerItem<'input>> {
+        let bracket_idx = start_idx + prefix.len();
+        for _ in 0..=prefix.len() {
+            _ = self.consume();
+        }
+        // Only the bracket itself is reported if it's closed by the wrong kind
+        if let Tok::VectorStart(bracket) | Tok::BytevectorStart(bracket) = tok {
+            self.open_brackets.push((bracket, bracket_idx));
+        }
+
+        return Some(Ok((start_idx, tok, bracket_idx + 1)));
+    }
+
+    fn consume_right_paren(&mut self, start_idx: usize, ch: char) -> Option<LexerItem<'input>> {
+        let close_kind = Bracket::from_close_char(ch)?;
+        let item = self.consume_single_char(start_idx, Tok::RightParen(close_kind));
+
+        // Unbalanced closers are left for the parser to report
+        match self.open_brackets.pop() {
+            Some((open_kind, open_idx)) if open_kind != close_kind => {
+                self.pending = item;
+                Some(Err(LexicalError::MismatchedBracket {
+                    open: Span::new(open_idx, open_idx + 1),
+                    open_kind,
+                    close: Span::new(start_idx, start_idx + 1),
+                    close_kind,
+                }))
+            }
+            _ => item,
+        }
     }
 
     fn consume_quote(&mut self, start_idx: usize) -> Option<LexerItem<'input>> {
         self.consume_single_char(start_idx, Tok::Quote)
     }
 
+    fn consume_quasiquote(&mut self, start_idx: usize) -> Option<LexerItem<'input>> {
+        self.consume_single_char(start_idx, Tok::Quasiquote)
+    }
+
+    #[allow(clippy::unnecessary_wraps)]
+    fn consume_unquote(&mut self, start_idx: usize) -> Option<LexerItem<'input>> {
+        _ = self.consume(); // consume ,
+        if self.chars.next_if(|(_, c)| *c == '@').is_some() {
+            return Some(Ok((start_idx, Tok::UnquoteSplicing, start_idx + 2)));
+        }
+
+        return Some(Ok((start_idx, Tok::Unquote, start_idx + 1)));
+    }
+
     // In case we change our minds later
     #[must_use]
     pub fn is_symbol_char(ch: char) -> bool {
         !Self::is_whitespace(ch)
+            && !ch.is_whitespace()
+            && !ch.is_control()
+            && !Self::is_comment_char(ch)
             && !Self::is_quote(ch)
+            && !Self::is_quasiquote(ch)
+            && !Self::is_unquote(ch)
             && !Self::is_string_quote(ch)
             && !Self::is_left_paren(ch)
             && !Self::is_right_paren(ch)
     }
 
+    /// Whether `s` has the syntax of a number. One out of range, like
+    /// `#e1e99999999`, is still a literal, the parser reports it.
+    #[must_use]
+    pub fn is_number_literal(s: &str) -> bool {
+        parse_number(s) != Err(LiteralError::Malformed)
+    }
+
+    /// A radix or exactness prefix followed by a digit, like `#x1` or `#e5`.
+    /// Other hash literals may start with the same letters.
+    #[must_use]
+    pub fn is_number_prefix(s: &str) -> bool {
+        let mut chars = s.chars().skip(1);
+        let is_prefix = chars.next().is_some_and(|c| "xobdeiXOBDEI".contains(c));
+        is_prefix && chars.next().is_some_and(Self::is_decimal_digit)
+    }
+
     #[must_use]
     pub const fn is_decimal_digit(ch: char) -> bool {
         ch.is_ascii_digit()
@@ -175,11 +539,31 @@ impl<'input> Lexer<'input> {
         '"' == ch
     }
 
+    #[must_use]
+    pub const fn is_escape_char(ch: char) -> bool {
+        '\\' == ch
+    }
+
     #[must_use]
     pub const fn is_quote(ch: char) -> bool {
         '\'' == ch
     }
 
+    #[must_use]
+    pub const fn is_quasiquote(ch: char) -> bool {
+        '`' == ch
+    }
+
+    #[must_use]
+    pub const fn is_unquote(ch: char) -> bool {
+        ',' == ch
+    }
+
+    #[must_use]
+    pub const fn is_comment_char(ch: char) -> bool {
+        ';' == ch
+    }
+
     #[must_use]
     pub const fn is_hash_char(ch: char) -> bool {
         '#' == ch
@@ -197,15 +581,13 @@ impl<'input> Lexer<'input> {
     }
 
     #[must_use]
-    pub fn is_left_paren(ch: char) -> bool {
-        const LEFT_PAREN_CHARS: [char; 3] = ['(', '[', '{'];
-        return LEFT_PAREN_CHARS.contains(&ch);
+    pub const fn is_left_paren(ch: char) -> bool {
+        Bracket::from_open_char(ch).is_some()
     }
 
     #[must_use]
-    pub fn is_right_paren(ch: char) -> bool {
-        const RIGHT_PAREN_CHARS: [char; 3] = [')', ']', '}'];
-        return RIGHT_PAREN_CHARS.contains(&ch);
+    pub const fn is_right_paren(ch: char) -> bool {
+        Bracket::from_close_char(ch).is_some()
     }
 }
 
@@ -213,25 +595,56 @@ impl<'input> Iterator for Lexer<'input> {
     type Item = LexerItem<'input>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if let Some(item) = self.pending.take() {
+            return Some(item);
+        }
+
         loop {
             let ch = self.chars.peek().copied();
             return match ch {
-                Some((i, c)) if Lexer::is_left_paren(c) => self.consume_left_paren(i),
-                Some((i, c)) if Lexer::is_right_paren(c) => self.consume_right_paren(i),
+                Some((i, c)) if Lexer::is_left_paren(c) => self.consume_left_paren(i, c),
+                Some((i, c)) if Lexer::is_right_paren(c) => self.consume_right_paren(i, c),
                 Some((i, c)) if Lexer::is_quote(c) => self.consume_quote(i),
+                Some((i, c)) if Lexer::is_quasiquote(c) => self.consume_quasiquote(i),
+                Some((i, c)) if Lexer::is_unquote(c) => self.consume_unquote(i),
                 Some((i, c)) if Lexer::is_string_quote(c) => self.consume_string(i),
                 Some((i, c)) if Lexer::is_minus_char(c) => self.consume_symbol_or_neg_num(i),
                 Some((i, c)) if Lexer::is_decimal_digit(c) => self.consume_num(i),
+                Some((i, c)) if Lexer::is_comment_char(c) => {
+                    let comment = self.consume_line_comment(i);
+                    if self.keep_trivia {
+                        return comment;
+                    }
+                    // Comments are skipped just like whitespace
+                    continue;
+                }
+                Some((i, c)) if Lexer::is_hash_char(c) && self.input[i + 1..].starts_with('|') => {
+                    match self.consume_block_comment(i) {
+                        Some(Ok(_)) if !self.keep_trivia => continue,
+                        comment => comment,
+                    }
+                }
+                Some((i, c)) if Lexer::is_hash_char(c) && self.input[i + 1..].starts_with(';') => {
+                    match self.consume_datum_comment(i) {
+                        Some(Ok(_)) if !self.keep_trivia => continue,
+                        comment => comment,
+                    }
+                }
                 Some((i, c)) if Lexer::is_hash_char(c) => self.consume_hash_symbol(i),
                 Some((i, c)) if Lexer::is_symbol_char(c) => self.consume_symbol(i),
                 Some((i, c)) if Lexer::is_whitespace(c) => {
-                    self.consume_whitespace(i);
+                    let whitespace = self.consume_whitespace(i);
+                    if self.keep_trivia {
+                        return whitespace;
+                    }
                     // This skips the whitespace instead of creating a token for it
                     continue;
-                    // return Some(Ok((i, Tok::Whitespace, i + 1)));
                 }
                 None => None, // End of file
-                Some((i, c)) => panic!("Tokenizer: invalid token {c} at {i}"),
+                Some((i, c)) => {
+                    _ = self.consume();
+                    Some(Err(LexicalError::InvalidCharacter { ch: c, pos: i }))
+                }
             };
         }
     }
@@ -244,17 +657,26 @@ fn lexer_works_properly() {
     Lexer::new(source).for_each(|t| println!("{:?}", t.unwrap().1));
 
     let mut lex = Lexer::new(source);
-    assert_eq!(lex.next().unwrap().unwrap().1, Tok::LeftParen);
+    assert_eq!(
+        lex.next().unwrap().unwrap().1,
+        Tok::LeftParen(Bracket::Round)
+    );
     assert_eq!(lex.next().unwrap().unwrap().1, Tok::Symbol("atom"));
     assert_eq!(lex.next().unwrap().unwrap().1, Tok::Num("10"));
     assert_eq!(lex.next().unwrap().unwrap().1, Tok::Str("string"));
 
     assert_eq!(lex.next().unwrap().unwrap().1, Tok::Quote);
-    assert_eq!(lex.next().unwrap().unwrap().1, Tok::LeftParen);
+    assert_eq!(
+        lex.next().unwrap().unwrap().1,
+        Tok::LeftParen(Bracket::Round)
+    );
     assert_eq!(lex.next().unwrap().unwrap().1, Tok::Num("1"));
     assert_eq!(lex.next().unwrap().unwrap().1, Tok::Num("2"));
     assert_eq!(lex.next().unwrap().unwrap().1, Tok::Num("3"));
-    assert_eq!(lex.next().unwrap().unwrap().1, Tok::RightParen);
+    assert_eq!(
+        lex.next().unwrap().unwrap().1,
+        Tok::RightParen(Bracket::Round)
+    );
 
     assert_eq!(lex.next().unwrap().unwrap().1, Tok::Symbol("string-append"));
 
@@ -262,7 +684,417 @@ fn lexer_works_properly() {
 
     assert_eq!(lex.next().unwrap().unwrap().1, Tok::Num("-10"));
 
-    assert_eq!(lex.next().unwrap().unwrap().1, Tok::RightParen);
+    assert_eq!(
+        lex.next().unwrap().unwrap().1,
+        Tok::RightParen(Bracket::Round)
+    );
 
     assert_eq!(Lexer::new(source).count(), 14);
 }
+
+#[test]
+fn lexer_reports_unterminated_string() {
+    let mut lex = Lexer::new("(display \"oops)");
+    assert_eq!(
+        lex.next().unwrap().unwrap().1,
+        Tok::LeftParen(Bracket::Round)
+    );
+    assert_eq!(lex.next().unwrap().unwrap().1, Tok::Symbol("display"));
+    assert_eq!(
+        lex.next().unwrap(),
+        Err(LexicalError::UnterminatedString { start: 9 })
+    );
+}
+
+#[test]
+fn lexer_reports_invalid_character() {
+    let errors: Vec<LexicalError> = Lexer::new("(a \u{7} b)").filter_map(Result::err).collect();
+    assert_eq!(
+        errors,
+        [LexicalError::InvalidCharacter {
+            ch: '\u{7}',
+            pos: 3
+        }]
+    );
+}
+
+#[test]
+fn lexer_reports_malformed_number() {
+    let errors: Vec<LexicalError> = Lexer::new("(+ 12abc -3x 4)")
+        .filter_map(Result::err)
+        .collect();
+    assert_eq!(
+        errors,
+        [
+            LexicalError::MalformedNumber {
+                span: Span::new(3, 8)
+            },
+            LexicalError::MalformedNumber {
+                span: Span::new(9, 12)
+            }
+        ]
+    );
+}
+
+#[test]
+fn number_tokens() {
+    let toks: Vec<Tok> = Lexer::new("1.5 1e10 3/4 -2.5 .5 +7 #x1F #e1.5 + ... #t")
+        .map(|item| item.unwrap().1)
+        .collect();
+    assert_eq!(
+        toks,
+        [
+            Tok::Num("1.5"),
+            Tok::Num("1e10"),
+            Tok::Num("3/4"),
+            Tok::Num("-2.5"),
+            Tok::Num(".5"),
+            Tok::Num("+7"),
+            Tok::Num("#x1F"),
+            Tok::Num("#e1.5"),
+            Tok::Symbol("+"),
+            Tok::Symbol("..."),
+            Tok::HashSymbol("#t"),
+        ]
+    );
+
+    let errors: Vec<LexicalError> = Lexer::new("#b102 #xyz").filter_map(Result::err).collect();
+    assert_eq!(
+        errors,
+        [
+            LexicalError::MalformedNumber {
+                span: Span::new(0, 5)
+            },
+            LexicalError::UnknownHashLiteral {
+                span: Span::new(6, 10)
+            }
+        ]
+    );
+}
+
+#[test]
+fn lexer_reports_unknown_hash_literal() {
+    let mut lex = Lexer::new("#maybe");
+    assert_eq!(
+        lex.next().unwrap(),
+        Err(LexicalError::UnknownHashLiteral {
+            span: Span::new(0, 6)
+        })
+    );
+    assert_eq!(lex.next(), None);
+}
+
+#[test]
+fn hash_dispatch_tokens() {
+    let toks: Vec<Tok> = Lexer::new("#date\"2024-01-01\" #rx[a] #t(1)")
+        .map(|t| t.unwrap().1)
+        .collect();
+    assert_eq!(
+        toks,
+        [
+            Tok::HashDispatch("#date"),
+            Tok::Str("2024-01-01"),
+            Tok::HashDispatch("#rx"),
+            Tok::LeftParen(Bracket::Square),
+            Tok::Symbol("a"),
+            Tok::RightParen(Bracket::Square),
+            Tok::HashSymbol("#t"),
+            Tok::LeftParen(Bracket::Round),
+            Tok::Num("1"),
+            Tok::RightParen(Bracket::Round),
+        ]
+    );
+
+    // Without a datum right after it, the name is still unknown
+    assert_eq!(
+        Lexer::new("#date \"x\"").next().unwrap(),
+        Err(LexicalError::UnknownHashLiteral {
+            span: Span::new(0, 5)
+        })
+    );
+    assert_eq!(
+        Lexer::new("#\"x\"").next().unwrap(),
+        Err(LexicalError::UnknownHashLiteral {
+            span: Span::new(0, 1)
+        })
+    );
+}
+
+#[test]
+fn lexer_keeps_minus_symbols() {
+    let toks: Vec<Tok> = Lexer::new("(- -x -1)").map(|t| t.unwrap().1).collect();
+    assert_eq!(
+        toks,
+        [
+            Tok::LeftParen(Bracket::Round),
+            Tok::Symbol("-"),
+            Tok::Symbol("-x"),
+            Tok::Num("-1"),
+            Tok::RightParen(Bracket::Round)
+        ]
+    );
+}
+
+#[test]
+fn lexer_skips_line_comments() {
+    let toks: Vec<Tok> = Lexer::new("; header\n(a ; trailing ; still comment\n b);end")
+        .map(|t| t.unwrap().1)
+        .collect();
+    assert_eq!(
+        toks,
+        [
+            Tok::LeftParen(Bracket::Round),
+            Tok::Symbol("a"),
+            Tok::Symbol("b"),
+            Tok::RightParen(Bracket::Round)
+        ]
+    );
+}
+
+#[test]
+fn lexer_skips_nested_block_comments() {
+    let toks: Vec<Tok> = Lexer::new("(a #| one #| two |# still one |# b)")
+        .map(|t| t.unwrap().1)
+        .collect();
+    assert_eq!(
+        toks,
+        [
+            Tok::LeftParen(Bracket::Round),
+            Tok::Symbol("a"),
+            Tok::Symbol("b"),
+            Tok::RightParen(Bracket::Round)
+        ]
+    );
+
+    let mut lex = Lexer::new("(a #| #| |# b)");
+    assert_eq!(
+        lex.next().unwrap().unwrap().1,
+        Tok::LeftParen(Bracket::Round)
+    );
+    assert_eq!(lex.next().unwrap().unwrap().1, Tok::Symbol("a"));
+    assert_eq!(
+        lex.next().unwrap(),
+        Err(LexicalError::UnterminatedBlockComment { start: 3 })
+    );
+}
+
+#[test]
+fn lexer_skips_datum_comments() {
+    let toks: Vec<Tok> = Lexer::new("(a #;(b (c) \"d\") #; 'e #;#;f g h)")
+        .map(|t| t.unwrap().1)
+        .collect();
+    assert_eq!(
+        toks,
+        [
+            Tok::LeftParen(Bracket::Round),
+            Tok::Symbol("a"),
+            Tok::Symbol("h"),
+            Tok::RightParen(Bracket::Round)
+        ]
+    );
+
+    let items: Vec<_> = Lexer::new("[(a #;) b]").collect();
+    assert_eq!(
+        items,
+        [
+            Ok((0, Tok::LeftParen(Bracket::Square), 1)),
+            Ok((1, Tok::LeftParen(Bracket::Round), 2)),
+            Ok((2, Tok::Symbol("a"), 3)),
+            Err(LexicalError::MissingCommentedDatum {
+                span: Span::new(4, 6)
+            }),
+            Ok((6, Tok::RightParen(Bracket::Round), 7)),
+            Ok((8, Tok::Symbol("b"), 9)),
+            Ok((9, Tok::RightParen(Bracket::Square), 10)),
+        ]
+    );
+
+    let errors: Vec<LexicalError> = Lexer::new("#; (a b").filter_map(Result::err).collect();
+    assert_eq!(
+        errors,
+        [LexicalError::MissingCommentedDatum {
+            span: Span::new(0, 7)
+        }]
+    );
+}
+
+#[test]
+fn lexer_keeps_escaped_quotes_in_strings() {
+    let source = r#"("say \"hi\"" "a\\" "line\nbreak")"#;
+    let toks: Vec<Tok> = Lexer::new(source).map(|t| t.unwrap().1).collect();
+    assert_eq!(
+        toks,
+        [
+            Tok::LeftParen(Bracket::Round),
+            Tok::Str(r#"say \"hi\""#),
+            Tok::Str(r"a\\"),
+            Tok::Str(r"line\nbreak"),
+            Tok::RightParen(Bracket::Round)
+        ]
+    );
+}
+
+#[test]
+fn lexer_reports_invalid_escape() {
+    let mut lex = Lexer::new(r#"(display "bad \q")"#);
+    assert_eq!(
+        lex.next().unwrap().unwrap().1,
+        Tok::LeftParen(Bracket::Round)
+    );
+    assert_eq!(lex.next().unwrap().unwrap().1, Tok::Symbol("display"));
+    assert_eq!(
+        lex.next().unwrap(),
+        Err(LexicalError::InvalidEscape {
+            span: Span::new(14, 16)
+        })
+    );
+    assert_eq!(
+        lex.next().unwrap().unwrap().1,
+        Tok::RightParen(Bracket::Round)
+    );
+}
+
+#[test]
+fn lexer_keeps_bracket_kinds() {
+    let toks: Vec<Tok> = Lexer::new("[{()}]").map(|t| t.unwrap().1).collect();
+    assert_eq!(
+        toks,
+        [
+            Tok::LeftParen(Bracket::Square),
+            Tok::LeftParen(Bracket::Curly),
+            Tok::LeftParen(Bracket::Round),
+            Tok::RightParen(Bracket::Round),
+            Tok::RightParen(Bracket::Curly),
+            Tok::RightParen(Bracket::Square)
+        ]
+    );
+}
+
+#[test]
+fn lexer_reports_mismatched_brackets() {
+    let items: Vec<LexerItem> = Lexer::new("(+ 1 2]").collect();
+    assert_eq!(
+        items[4],
+        Err(LexicalError::MismatchedBracket {
+            open: Span::new(0, 1),
+            open_kind: Bracket::Round,
+            close: Span::new(6, 7),
+            close_kind: Bracket::Square
+        })
+    );
+    // The closer itself is still produced after the error
+    assert_eq!(items[5], Ok((6, Tok::RightParen(Bracket::Square), 7)));
+    assert_eq!(items.len(), 6);
+}
+
+#[test]
+fn trivia_lexer_covers_whole_input() {
+    let source = "; note\n(a #| block |# b #;(c) [d])\n";
+    let items: Vec<(usize, Tok, usize)> = Lexer::with_trivia(source).map(Result::unwrap).collect();
+
+    let mut expected_start = 0;
+    for (start, _, end) in &items {
+        assert_eq!(*start, expected_start);
+        expected_start = *end;
+    }
+    assert_eq!(expected_start, source.len());
+
+    let comments: Vec<Tok> = items
+        .into_iter()
+        .map(|item| item.1)
+        .filter(|tok| matches!(tok, Tok::Comment(_)))
+        .collect();
+    assert_eq!(
+        comments,
+        [
+            Tok::Comment("; note"),
+            Tok::Comment("#| block |#"),
+            Tok::Comment("#;(c)")
+        ]
+    );
+}
+
+#[test]
+fn quasiquote_tokens() {
+    let toks: Vec<Tok> = Lexer::new("`(a ,b ,@c d,e)")
+        .map(|item| item.unwrap().1)
+        .collect();
+
+    assert_eq!(
+        toks,
+        [
+            Tok::Quasiquote,
+            Tok::LeftParen(Bracket::Round),
+            Tok::Symbol("a"),
+            Tok::Unquote,
+            Tok::Symbol("b"),
+            Tok::UnquoteSplicing,
+            Tok::Symbol("c"),
+            Tok::Symbol("d"),
+            Tok::Unquote,
+            Tok::Symbol("e"),
+            Tok::RightParen(Bracket::Round),
+        ]
+    );
+}
+
+#[test]
+fn char_literal_tokens() {
+    let toks: Vec<Tok> = Lexer::new("(#\\( #\\) #\\  #\\space #\\;)")
+        .map(|item| item.unwrap().1)
+        .collect();
+
+    assert_eq!(
+        toks,
+        [
+            Tok::LeftParen(Bracket::Round),
+            Tok::HashSymbol("#\\("),
+            Tok::HashSymbol("#\\)"),
+            Tok::HashSymbol("#\\ "),
+            Tok::HashSymbol("#\\space"),
+            Tok::HashSymbol("#\\;"),
+            Tok::RightParen(Bracket::Round),
+        ]
+    );
+    assert_eq!(
+        Lexer::new("#\\").next().unwrap(),
+        Err(LexicalError::UnknownCharName {
+            span: Span::new(0, 2)
+        })
+    );
+}
+
+#[test]
+fn vector_start_tokens() {
+    let toks: Vec<Tok> = Lexer::new("#(a #u8[1])")
+        .map(|item| item.unwrap().1)
+        .collect();
+    assert_eq!(
+        toks,
+        [
+            Tok::VectorStart(Bracket::Round),
+            Tok::Symbol("a"),
+            Tok::BytevectorStart(Bracket::Square),
+            Tok::Num("1"),
+            Tok::RightParen(Bracket::Square),
+            Tok::RightParen(Bracket::Round),
+        ]
+    );
+
+    assert_eq!(
+        Lexer::new("#u8 1").next().unwrap(),
+        Err(LexicalError::UnknownHashLiteral {
+            span: Span::new(0, 3)
+        })
+    );
+    let err = Lexer::new("#u8(1]").find_map(Result::err).unwrap();
+    assert_eq!(
+        err,
+        LexicalError::MismatchedBracket {
+            open: Span::new(3, 4),
+            open_kind: Bracket::Round,
+            close: Span::new(5, 6),
+            close_kind: Bracket::Square,
+        }
+    );
+}
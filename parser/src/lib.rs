@@ -8,21 +8,98 @@
 #![allow(clippy::missing_errors_doc)]
 #[allow(clippy::missing_panics_doc)]
 pub mod ast;
+pub mod cst;
+pub mod diagnostics;
+pub mod dispatch;
+pub mod escape;
+pub mod format;
 pub mod lexer;
+pub mod number;
+pub mod reader;
+pub mod span;
 
-use ast::Node;
+use ast::{Node, SpannedNode};
+use diagnostics::Diagnostic;
+use dispatch::DispatchTable;
 use lalrpop_util::{lalrpop_mod, ParseError};
 use lexer::{Lexer, LexicalError, Tok};
 
 lalrpop_mod!(pub ceceo);
 
-pub fn parse_ceceo(
-    input: &str,
-) -> Result<Vec<Vec<Node>>, ParseError<usize, Tok<'_>, LexicalError>> {
+pub type CeceoParseError<'input> = ParseError<usize, Tok<'input>, LexicalError>;
+
+pub fn parse_ceceo(input: &str) -> Result<Vec<Node>, CeceoParseError<'_>> {
+    parse_ceceo_with(input, &DispatchTable::new())
+}
+
+/// Like `parse_ceceo`, also reading the custom `#name` literals of `dispatch`.
+pub fn parse_ceceo_with<'input>(
+    input: &'input str,
+    dispatch: &DispatchTable,
+) -> Result<Vec<Node>, CeceoParseError<'input>> {
+    let program = parse_ceceo_spanned_with(input, dispatch)?;
+
+    Ok(program.iter().map(SpannedNode::to_node).collect())
+}
+
+/// Like `parse_ceceo`, but every node keeps its byte span in `input`.
+pub fn parse_ceceo_spanned(input: &str) -> Result<Vec<SpannedNode>, CeceoParseError<'_>> {
+    parse_ceceo_spanned_with(input, &DispatchTable::new())
+}
+
+/// Like `parse_ceceo_spanned`, also reading the custom `#name` literals of
+/// `dispatch`. Their expansions get the span of the whole literal.
+pub fn parse_ceceo_spanned_with<'input>(
+    input: &'input str,
+    dispatch: &DispatchTable,
+) -> Result<Vec<SpannedNode>, CeceoParseError<'input>> {
+    let mut errors = Vec::new();
     let lexer = Lexer::new(input);
     let ep = ceceo::ProgramParser::new();
 
-    ep.parse(input, lexer)
+    let program = ep.parse(input, &mut errors, dispatch, lexer)?;
+    match errors.into_iter().next() {
+        Some(recovery) => Err(recovery.error),
+        None => Ok(program),
+    }
+}
+
+/// Parses as much of `input` as possible instead of stopping at the first
+/// error. Malformed forms are skipped, and every error found is returned
+/// in source order alongside the forms that did parse.
+#[must_use]
+pub fn parse_ceceo_recovering(input: &str) -> (Vec<Node>, Vec<CeceoParseError<'_>>) {
+    let mut errors = Vec::new();
+    let mut lexical_errors = Vec::new();
+    // The parser can't recover from lexical errors, so bad tokens are dropped
+    // here and reported together with the syntax errors
+    let lexer = Lexer::new(input).filter(|item| match item {
+        Ok(_) => true,
+        Err(err) => {
+            lexical_errors.push(err.clone());
+            false
+        }
+    });
+    let ep = ceceo::ProgramParser::new();
+
+    let result = ep.parse(input, &mut errors, &DispatchTable::new(), lexer);
+    let mut errors: Vec<_> = errors.into_iter().map(|recovery| recovery.error).collect();
+    errors.extend(
+        lexical_errors
+            .into_iter()
+            .map(|error| ParseError::User { error }),
+    );
+
+    let program = match result {
+        Ok(program) => program.iter().map(SpannedNode::to_node).collect(),
+        Err(err) => {
+            errors.push(err);
+            Vec::new()
+        }
+    };
+    errors.sort_by_cached_key(|err| Diagnostic::from_parse_error(err).span.start);
+
+    return (program, errors);
 }
 
 #[test]
@@ -31,7 +108,9 @@ fn basic_expr_test() {
     let lexer = Lexer::new(input);
     let ep = ceceo::ProgramParser::new();
 
-    let parsed_expr = ep.parse(input, lexer).unwrap();
+    let parsed_expr = ep
+        .parse(input, &mut Vec::new(), &DispatchTable::new(), lexer)
+        .unwrap();
     print!("{parsed_expr:?}");
 }
 
@@ -41,7 +120,9 @@ fn recursive_expr_test() {
     let lexer = Lexer::new(input);
     let ep = ceceo::ProgramParser::new();
 
-    let parsed_expr = ep.parse(input, lexer).unwrap();
+    let parsed_expr = ep
+        .parse(input, &mut Vec::new(), &DispatchTable::new(), lexer)
+        .unwrap();
     print!("{parsed_expr:?}");
 }
 
@@ -51,7 +132,9 @@ fn multiple_expr_test() {
     let lexer = Lexer::new(input);
     let ep = ceceo::ProgramParser::new();
 
-    let parsed_expr = ep.parse(input, lexer).unwrap();
+    let parsed_expr = ep
+        .parse(input, &mut Vec::new(), &DispatchTable::new(), lexer)
+        .unwrap();
     print!("{parsed_expr:?}");
 }
 
@@ -63,7 +146,9 @@ fn simple_program_test() {
 
     let x: Vec<char> = input.chars().skip(57).collect();
     println!("{x:?}");
-    let parsed_expr = ep.parse(input, lexer).unwrap();
+    let parsed_expr = ep
+        .parse(input, &mut Vec::new(), &DispatchTable::new(), lexer)
+        .unwrap();
     print!("{parsed_expr:?}");
 }
 
@@ -81,7 +166,7 @@ fn real_program_test() {
     let lexer = Lexer::new(input);
     let ep = ceceo::ProgramParser::new();
 
-    match ep.parse(input, lexer) {
+    match ep.parse(input, &mut Vec::new(), &DispatchTable::new(), lexer) {
         Ok(parsed_expr) => println!("{parsed_expr:?}"),
         Err(err) => {
             println!("{err:?}");
@@ -96,7 +181,9 @@ fn quote_list_test() {
     let lexer = Lexer::new(input);
     let ep = ceceo::ProgramParser::new();
 
-    let parsed_expr = ep.parse(input, lexer).unwrap();
+    let parsed_expr = ep
+        .parse(input, &mut Vec::new(), &DispatchTable::new(), lexer)
+        .unwrap();
     print!("{parsed_expr:?}");
 }
 
@@ -106,7 +193,9 @@ fn quote_atom_test() {
     let lexer = Lexer::new(input);
     let ep = ceceo::ProgramParser::new();
 
-    let parsed_expr = ep.parse(input, lexer).unwrap();
+    let parsed_expr = ep
+        .parse(input, &mut Vec::new(), &DispatchTable::new(), lexer)
+        .unwrap();
     print!("{parsed_expr:?}");
 }
 
@@ -116,7 +205,9 @@ fn different_parens_test() {
     let lexer = Lexer::new(input);
     let ep = ceceo::ProgramParser::new();
 
-    let parsed_expr = ep.parse(input, lexer).unwrap();
+    let parsed_expr = ep
+        .parse(input, &mut Vec::new(), &DispatchTable::new(), lexer)
+        .unwrap();
     print!("{parsed_expr:?}");
 }
 
@@ -126,7 +217,9 @@ fn different_parens_content_test() {
     let lexer = Lexer::new(input);
     let ep = ceceo::ProgramParser::new();
 
-    let parsed_expr = ep.parse(input, lexer).unwrap();
+    let parsed_expr = ep
+        .parse(input, &mut Vec::new(), &DispatchTable::new(), lexer)
+        .unwrap();
     print!("{parsed_expr:?}");
 }
 
@@ -136,6 +229,412 @@ fn hash_symbol_test() {
     let lexer = Lexer::new(input);
     let ep = ceceo::ProgramParser::new();
 
-    let parsed_expr = ep.parse(input, lexer).unwrap();
+    let parsed_expr = ep
+        .parse(input, &mut Vec::new(), &DispatchTable::new(), lexer)
+        .unwrap();
+    print!("{parsed_expr:?}");
+}
+
+#[test]
+fn top_level_datums_test() {
+    let input = "42 'foo bar \"str\" '(1 2) #t (display bar)";
+    let lexer = Lexer::new(input);
+    let ep = ceceo::ProgramParser::new();
+
+    let parsed_expr = ep
+        .parse(input, &mut Vec::new(), &DispatchTable::new(), lexer)
+        .unwrap();
+    assert_eq!(parsed_expr.len(), 7);
     print!("{parsed_expr:?}");
 }
+
+#[test]
+fn empty_program_test() {
+    assert_eq!(parse_ceceo("").unwrap(), []);
+    assert_eq!(parse_ceceo("  ; only a comment\n").unwrap(), []);
+}
+
+#[test]
+fn comments_test() {
+    let input = "
+    ; Prints a greeting
+    (display \"hi\") ; trailing
+    #| (display \"block\")
+       #| nested |# |#
+    (display #;(ignored) \"bye\")";
+
+    let parsed_expr = parse_ceceo(input).unwrap();
+    assert_eq!(
+        parsed_expr,
+        parse_ceceo("(display \"hi\") (display \"bye\")").unwrap()
+    );
+}
+
+#[test]
+fn string_escapes_test() {
+    let input = r#"(display "line one\n\t\"quoted\" \x3BB;\
+                   \u{1F600}")"#;
+
+    let parsed_expr = parse_ceceo(input).unwrap();
+    let Node::List(display) = &parsed_expr[0] else {
+        panic!("Expected a list, got {parsed_expr:?}");
+    };
+    assert_eq!(
+        display[1],
+        Node::Atom(ast::Atom::Str("line one\n\t\"quoted\" λ😀".to_string()))
+    );
+    assert_eq!(display[1].to_string(), r#""line one\n\t\"quoted\" λ😀""#);
+}
+
+#[test]
+fn mismatched_parens_test() {
+    let input = "(+ 1 2]";
+    let err = parse_ceceo(input).unwrap_err();
+
+    assert_eq!(
+        err,
+        ParseError::User {
+            error: LexicalError::MismatchedBracket {
+                open: span::Span::new(0, 1),
+                open_kind: ast::Bracket::Round,
+                close: span::Span::new(6, 7),
+                close_kind: ast::Bracket::Square
+            }
+        }
+    );
+}
+
+#[test]
+fn bracket_kinds_are_recorded() {
+    let input = "(cond [(zero? x) '{1}] [else 2])";
+    let program = parse_ceceo_spanned(input).unwrap();
+
+    let ast::SpannedKind::List(outer, clauses) = &program[0].kind else {
+        panic!("Expected a list, got {program:?}");
+    };
+    assert_eq!(*outer, ast::Bracket::Round);
+    assert!(matches!(
+        clauses[1].kind,
+        ast::SpannedKind::List(ast::Bracket::Square, _)
+    ));
+    assert!(matches!(
+        clauses[1].children()[1].kind,
+        ast::SpannedKind::QuoteList(ast::Bracket::Curly, _)
+    ));
+}
+
+#[test]
+fn recovering_parse_continues_after_mismatched_bracket() {
+    let (program, errors) = parse_ceceo_recovering("(display [+ 1 2)) (display 3)");
+
+    assert_eq!(errors.len(), 1);
+    assert_eq!(
+        program,
+        parse_ceceo("(display [+ 1 2]) (display 3)").unwrap()
+    );
+}
+
+#[test]
+fn spans_cover_source_text() {
+    let input = "(display \"hi\")\n('(1 2) 'sym -10)";
+    let program = parse_ceceo_spanned(input).unwrap();
+
+    let text = |node: &SpannedNode| node.span.source_text(input);
+    assert_eq!(text(&program[0]), "(display \"hi\")");
+    assert_eq!(text(&program[0].children()[1]), "\"hi\"");
+    assert_eq!(text(&program[1]), "('(1 2) 'sym -10)");
+
+    let inner: Vec<&str> = program[1].children().iter().map(text).collect();
+    assert_eq!(inner, ["'(1 2)", "'sym", "-10"]);
+    assert_eq!(text(&program[1].children()[0].children()[1]), "2");
+
+    let index = span::LineIndex::new(input);
+    let quoted_sym = &program[1].children()[1];
+    assert_eq!(
+        index.line_col(quoted_sym.span.start),
+        span::LineCol { line: 2, column: 9 }
+    );
+}
+
+#[test]
+fn spanned_and_bare_trees_agree() {
+    let input = "(+ 1 (* 2 3)) (display '(a \"b\"))";
+    let bare = parse_ceceo(input).unwrap();
+    let spanned = parse_ceceo_spanned(input).unwrap();
+
+    let stripped: Vec<Node> = spanned.iter().map(SpannedNode::to_node).collect();
+    assert_eq!(stripped, bare);
+}
+
+#[test]
+fn out_of_range_byte_is_an_error() {
+    let input = "#u8(1 256)";
+    let err = parse_ceceo(input).unwrap_err();
+    assert_eq!(
+        err,
+        ParseError::User {
+            error: LexicalError::NumberOutOfRange {
+                span: span::Span::new(6, 9)
+            }
+        }
+    );
+}
+
+#[test]
+fn huge_exact_exponent_is_out_of_range() {
+    let input = "(display #e1e99999999)";
+    let err = parse_ceceo(input).unwrap_err();
+    assert_eq!(
+        err,
+        ParseError::User {
+            error: LexicalError::NumberOutOfRange {
+                span: span::Span::new(9, 21)
+            }
+        }
+    );
+}
+
+#[test]
+fn number_literal_test() {
+    use ast::Atom;
+    use number::Number;
+
+    let result = parse_ceceo("(+ 99999999999 1.5 -3/4 #x1F #b101 .5 +2)").unwrap();
+    let Node::List(items) = &result[0] else {
+        panic!("expected a list");
+    };
+
+    assert_eq!(
+        items[1],
+        Node::Atom(Atom::Num(Number::Integer(99_999_999_999_i64.into())))
+    );
+    assert_eq!(items[2], Node::Atom(Atom::Num(Number::Real(1.5))));
+    assert_eq!(items[4], Node::Atom(Atom::Num(31.into())));
+    assert_eq!(items[5], Node::Atom(Atom::Num(5.into())));
+    assert_eq!(items[6], Node::Atom(Atom::Num(Number::Real(0.5))));
+    assert_eq!(items[7], Node::Atom(Atom::Num(2.into())));
+    assert_eq!(result[0].to_string(), "(+ 99999999999 1.5 -3/4 31 5 0.5 2)");
+    assert_eq!(
+        parse_ceceo("#u8(#xFF)").unwrap(),
+        [Node::Bytevector(vec![255])]
+    );
+
+    assert!(parse_ceceo("1/0").is_err());
+    assert!(parse_ceceo("#x1G").is_err());
+    assert!(parse_ceceo("1.2.3").is_err());
+}
+
+#[test]
+fn unknown_hash_literal_is_an_error() {
+    let err = parse_ceceo("(display #foo)").unwrap_err();
+    assert!(matches!(
+        err,
+        ParseError::User {
+            error: LexicalError::UnknownHashLiteral { .. }
+        }
+    ));
+}
+
+#[test]
+fn parse_never_panics() {
+    let inputs = [
+        "",
+        "(",
+        ")",
+        "'",
+        "#",
+        "-",
+        "\"",
+        "(\"",
+        "#\\a",
+        "#(1 2)",
+        "99999999999 1/2 -1.5e3 #x-F #e.5",
+        "(- -)",
+        "(a . b)",
+        "(\u{0})",
+        "(λ \u{a0})",
+        "'(",
+        "(')",
+        "((((",
+        "))))",
+        "#e1e99999999",
+        "#e1e-99999999",
+    ];
+    let sample = "(define (f x) '(1 \"two\" #t -3)) (f [g {h}])";
+    let prefixes = (0..=sample.len()).filter_map(|idx| sample.get(..idx));
+
+    for input in inputs.into_iter().chain(prefixes) {
+        _ = parse_ceceo(input);
+    }
+}
+
+#[test]
+fn recovering_parse_reports_every_error() {
+    let input = "(display 1)\n(display (+ 1 2) ')\n(define x 10)\n(+ x #nope 1)\n(display x)";
+    let (program, errors) = parse_ceceo_recovering(input);
+
+    let rendered: Vec<Diagnostic> = errors.iter().map(Diagnostic::from_parse_error).collect();
+    assert_eq!(rendered.len(), 2);
+    assert_eq!(rendered[0].message, "unexpected `)`");
+    assert_eq!(rendered[1].message, "unknown hash literal");
+    assert!(rendered[0].span.start < rendered[1].span.start);
+
+    assert_eq!(program.last(), parse_ceceo("(display x)").unwrap().first());
+    assert!(program.contains(&parse_ceceo("(define x 10)").unwrap()[0]));
+}
+
+#[test]
+fn recovering_parse_matches_strict_parse_on_valid_input() {
+    let input = "(+ 1 (* 2 3)) (display '(a \"b\"))";
+    let (program, errors) = parse_ceceo_recovering(input);
+
+    assert!(errors.is_empty());
+    assert_eq!(program, parse_ceceo(input).unwrap());
+}
+
+#[test]
+fn strict_parse_reports_first_recovered_error() {
+    let input = "(display ')) (display 1)";
+    let err = parse_ceceo(input).unwrap_err();
+
+    assert_eq!(Diagnostic::from_parse_error(&err).span.start, 10);
+}
+
+#[test]
+fn recovering_parse_keeps_forms_before_missing_paren() {
+    let (program, errors) = parse_ceceo_recovering("(display 1)\n(define x (+ 1 2)\n(display x)");
+
+    assert_eq!(program, parse_ceceo("(display 1)").unwrap());
+    assert!(matches!(
+        errors.as_slice(),
+        [ParseError::UnrecognizedEOF { .. }]
+    ));
+}
+
+#[test]
+fn quasiquote_test() {
+    use ast::Atom;
+
+    let result = parse_ceceo("`(a ,b ,@(c d))").unwrap();
+    let sym = |s: &str| Node::Atom(Atom::Symbol(s.to_string()));
+
+    assert_eq!(
+        result,
+        vec![Node::Quasiquote(Box::new(Node::List(vec![
+            sym("a"),
+            Node::Unquote(Box::new(sym("b"))),
+            Node::UnquoteSplicing(Box::new(Node::List(vec![sym("c"), sym("d")]))),
+        ])))]
+    );
+    assert_eq!(result[0].to_string(), "`(a ,b ,@(c d))");
+}
+
+#[test]
+fn quote_takes_any_datum() {
+    use ast::Atom;
+
+    let sym = |s: &str| Node::Atom(Atom::Symbol(s.to_string()));
+    let tagged = |tag: &str, node: Node| Node::QuoteList(vec![sym(tag), node]);
+    let result = parse_ceceo("''a '`(a ,b) ',x ',@(x) ''(1 . 2) '#(1)").unwrap();
+
+    assert_eq!(
+        result,
+        vec![
+            tagged("quote", sym("a")),
+            tagged(
+                "quasiquote",
+                Node::List(vec![sym("a"), Node::Unquote(Box::new(sym("b")))])
+            ),
+            tagged("unquote", sym("x")),
+            tagged("unquote-splicing", Node::List(vec![sym("x")])),
+            tagged(
+                "quote",
+                Node::DottedList(
+                    vec![Node::Atom(Atom::Num(1.into()))],
+                    Box::new(Node::Atom(Atom::Num(2.into())))
+                )
+            ),
+            Node::Vector(vec![Node::Atom(Atom::Num(1.into()))]),
+        ]
+    );
+    assert_eq!(result[0].to_string(), "'(quote a)");
+    assert_eq!(
+        parse_ceceo_spanned("''a").unwrap()[0].span,
+        span::Span::new(0, 3)
+    );
+}
+
+#[test]
+fn dotted_pair_test() {
+    use ast::Atom;
+
+    let sym = |s: &str| Node::Atom(Atom::Symbol(s.to_string()));
+    let result = parse_ceceo("(a . b) '(1 2 . (3)) (x ... y)").unwrap();
+
+    assert_eq!(
+        result[0],
+        Node::DottedList(vec![sym("a")], Box::new(sym("b")))
+    );
+    assert_eq!(
+        result[1],
+        Node::QuoteDottedList(
+            vec![
+                Node::Atom(Atom::Num(1.into())),
+                Node::Atom(Atom::Num(2.into()))
+            ],
+            Box::new(Node::List(vec![Node::Atom(Atom::Num(3.into()))]))
+        )
+    );
+    assert_eq!(result[2], Node::List(vec![sym("x"), sym("..."), sym("y")]));
+    assert_eq!(result[0].to_string(), "(a . b)");
+
+    assert!(parse_ceceo("(. a)").is_err());
+    assert!(parse_ceceo("(a . b c)").is_err());
+    assert!(parse_ceceo("(a .)").is_err());
+}
+
+#[test]
+fn char_literal_test() {
+    use ast::Atom;
+
+    let result = parse_ceceo("(list #\\a #\\space #\\x3BB #\\))").unwrap();
+    let Node::List(items) = &result[0] else {
+        panic!("expected a list");
+    };
+
+    assert_eq!(
+        items[1..],
+        [
+            Node::Atom(Atom::Char('a')),
+            Node::Atom(Atom::Char(' ')),
+            Node::Atom(Atom::Char('λ')),
+            Node::Atom(Atom::Char(')')),
+        ]
+    );
+    assert_eq!(result[0].to_string(), "(list #\\a #\\space #\\λ #\\))");
+}
+
+#[test]
+fn vector_literal_test() {
+    use ast::Atom;
+
+    let result = parse_ceceo("#(1 a \"s\") #u8(0 255) '#[x]").unwrap();
+
+    assert_eq!(
+        result,
+        vec![
+            Node::Vector(vec![
+                Node::Atom(Atom::Num(1.into())),
+                Node::Atom(Atom::Symbol("a".to_string())),
+                Node::Atom(Atom::Str("s".to_string())),
+            ]),
+            Node::Bytevector(vec![0, 255]),
+            Node::Vector(vec![Node::Atom(Atom::Symbol("x".to_string()))]),
+        ]
+    );
+    assert_eq!(result[1].to_string(), "#u8(0 255)");
+
+    assert!(parse_ceceo("#u8(256)").is_err());
+    assert!(parse_ceceo("#u8((1))").is_err());
+    assert!(parse_ceceo("#(a]").is_err());
+}
@@ -0,0 +1,565 @@
+use std::{
+    cmp::Ordering,
+    fmt::Display,
+    hash::{Hash, Hasher},
+    ops::{Add, Mul, Neg, Sub},
+};
+
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_rational::BigRational;
+use num_traits::{Signed, ToPrimitive, Zero};
+
+/// A number of the numeric tower. Exact numbers are integers of any size and
+/// fractions, inexact numbers are floats.
+///
+/// Fractions are kept in lowest terms, and one whose denominator is 1 is
+/// always stored as an `Integer`.
+#[derive(Clone, Debug)]
+pub enum Number {
+    Integer(BigInt),
+    Rational(BigRational),
+    Real(f64),
+}
+
+/// Why an operation on numbers has no result
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NumericError {
+    DivisionByZero,
+    NotAnInteger,
+}
+
+impl Display for NumericError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DivisionByZero => write!(f, "Division by zero"),
+            Self::NotAnInteger => write!(f, "Incorrect type: Expected integer"),
+        }
+    }
+}
+
+/// Two numbers brought to the same level of the tower
+enum Promoted {
+    Integers(BigInt, BigInt),
+    Rationals(BigRational, BigRational),
+    Reals(f64, f64),
+}
+
+impl Number {
+    /// Builds an exact fraction, which is an `Integer` when it has no
+    /// fractional part.
+    #[must_use]
+    pub fn rational(ratio: BigRational) -> Self {
+        if ratio.is_integer() {
+            return Self::Integer(ratio.to_integer());
+        }
+
+        return Self::Rational(ratio);
+    }
+
+    #[must_use]
+    pub const fn is_exact(&self) -> bool {
+        !matches!(self, Self::Real(_))
+    }
+
+    #[must_use]
+    pub fn is_zero(&self) -> bool {
+        match self {
+            Self::Integer(n) => n.is_zero(),
+            Self::Rational(r) => r.is_zero(),
+            Self::Real(x) => *x == 0.0,
+        }
+    }
+
+    #[must_use]
+    pub fn is_positive(&self) -> bool {
+        match self {
+            Self::Integer(n) => n.is_positive(),
+            Self::Rational(r) => r.is_positive(),
+            Self::Real(x) => *x > 0.0,
+        }
+    }
+
+    /// The value of an exact integer
+    #[must_use]
+    pub const fn as_integer(&self) -> Option<&BigInt> {
+        match self {
+            Self::Integer(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    /// The nearest float, which is infinite for exact numbers too large to
+    /// fit in one.
+    #[must_use]
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Self::Integer(n) => n.to_f64(),
+            Self::Rational(r) => r.to_f64(),
+            Self::Real(x) => Some(*x),
+        }
+        .unwrap_or(f64::NAN)
+    }
+
+    /// Compares the values of two numbers of any kind, so `1`, `2/2` and
+    /// `1.0` are all equal. Only `NaN` is unordered.
+    #[must_use]
+    pub fn numeric_cmp(&self, other: &Self) -> Option<Ordering> {
+        match Self::promote(self.clone(), other.clone()) {
+            Promoted::Integers(a, b) => Some(a.cmp(&b)),
+            Promoted::Rationals(a, b) => Some(a.cmp(&b)),
+            Promoted::Reals(a, b) => a.partial_cmp(&b),
+        }
+    }
+
+    /// Divides, failing when `other` is an exact zero. There's no `/`
+    /// operator, as it would have to panic then. Dividing exact numbers gives an exact result, so `1 / 3` is `1/3`.
+    pub fn checked_div(self, other: Self) -> Result<Self, NumericError> {
+        match Self::promote(self, other) {
+            Promoted::Integers(_, b) if b.is_zero() => Err(NumericError::DivisionByZero),
+            Promoted::Rationals(_, b) if b.is_zero() => Err(NumericError::DivisionByZero),
+            Promoted::Integers(a, b) => Ok(Self::rational(BigRational::new(a, b))),
+            Promoted::Rationals(a, b) => Ok(Self::rational(a / b)),
+            Promoted::Reals(a, b) => Ok(Self::Real(a / b)),
+        }
+    }
+
+    /// `self` modulo `other`, which has the sign of `other`. Both must be
+    /// integers, and `other` can't be zero.
+    pub fn modulo(self, other: Self) -> Result<Self, NumericError> {
+        if other.is_zero() {
+            return Err(NumericError::DivisionByZero);
+        }
+
+        match Self::promote(self, other) {
+            Promoted::Integers(a, b) => Ok(Self::Integer(a.mod_floor(&b))),
+            Promoted::Reals(a, b) if a.fract() == 0.0 && b.fract() == 0.0 => {
+                Ok(Self::Real((-(a / b).floor()).mul_add(b, a)))
+            }
+            Promoted::Rationals(..) | Promoted::Reals(..) => Err(NumericError::NotAnInteger),
+        }
+    }
+
+    /// Moves both numbers to the lowest level of the tower that holds them:
+    /// any float makes both floats, otherwise any fraction makes both fractions.
+    fn promote(a: Self, b: Self) -> Promoted {
+        match (a, b) {
+            (Self::Integer(a), Self::Integer(b)) => Promoted::Integers(a, b),
+            (a @ Self::Real(_), b) | (a, b @ Self::Real(_)) => {
+                Promoted::Reals(a.to_f64(), b.to_f64())
+            }
+            (a, b) => Promoted::Rationals(a.into_ratio(), b.into_ratio()),
+        }
+    }
+
+    fn into_ratio(self) -> BigRational {
+        match self {
+            Self::Integer(n) => BigRational::from_integer(n),
+            Self::Rational(r) => r,
+            Self::Real(_) => unreachable!("floats are never promoted to fractions"),
+        }
+    }
+}
+
+macro_rules! impl_from_int {
+    ($($int:ty),*) => {
+        $(
+            impl From<$int> for Number {
+                fn from(n: $int) -> Self {
+                    Self::Integer(BigInt::from(n))
+                }
+            }
+        )*
+    };
+}
+
+impl_from_int!(i32, i64, u8, u32, usize);
+
+impl From<BigInt> for Number {
+    fn from(n: BigInt) -> Self {
+        Self::Integer(n)
+    }
+}
+
+impl From<BigRational> for Number {
+    fn from(ratio: BigRational) -> Self {
+        Self::rational(ratio)
+    }
+}
+
+impl From<f64> for Number {
+    fn from(x: f64) -> Self {
+        Self::Real(x)
+    }
+}
+
+impl Add for Number {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        match Self::promote(self, other) {
+            Promoted::Integers(a, b) => Self::Integer(a + b),
+            Promoted::Rationals(a, b) => Self::rational(a + b),
+            Promoted::Reals(a, b) => Self::Real(a + b),
+        }
+    }
+}
+
+impl Sub for Number {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        match Self::promote(self, other) {
+            Promoted::Integers(a, b) => Self::Integer(a - b),
+            Promoted::Rationals(a, b) => Self::rational(a - b),
+            Promoted::Reals(a, b) => Self::Real(a - b),
+        }
+    }
+}
+
+impl Mul for Number {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        match Self::promote(self, other) {
+            Promoted::Integers(a, b) => Self::Integer(a * b),
+            Promoted::Rationals(a, b) => Self::rational(a * b),
+            Promoted::Reals(a, b) => Self::Real(a * b),
+        }
+    }
+}
+
+impl Neg for Number {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        match self {
+            Self::Integer(n) => Self::Integer(-n),
+            Self::Rational(r) => Self::Rational(-r),
+            Self::Real(x) => Self::Real(-x),
+        }
+    }
+}
+
+/// Equality of the literals: `1` and `1.0` are different, use
+/// [`Number::numeric_cmp`] to compare values.
+impl PartialEq for Number {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Integer(a), Self::Integer(b)) => a == b,
+            (Self::Rational(a), Self::Rational(b)) => a == b,
+            // Bitwise, so that `NaN` is equal to itself and `Eq` holds
+            (Self::Real(a), Self::Real(b)) => a.to_bits() == b.to_bits(),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Number {}
+
+impl Hash for Number {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Self::Integer(n) => n.hash(state),
+            Self::Rational(r) => r.hash(state),
+            Self::Real(x) => x.to_bits().hash(state),
+        }
+    }
+}
+
+impl Display for Number {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Integer(n) => write!(f, "{n}"),
+            Self::Rational(r) => write!(f, "{r}"),
+            Self::Real(x) if x.is_nan() => write!(f, "+nan.0"),
+            Self::Real(x) if x.is_infinite() => {
+                write!(f, "{}inf.0", if x.is_sign_positive() { '+' } else { '-' })
+            }
+            // Debug keeps the `.0` of whole floats, so they read back as floats
+            Self::Real(x) => write!(f, "{x:?}"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Exactness {
+    Exact,
+    Inexact,
+}
+
+/// Why a string isn't read as a number
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LiteralError {
+    Malformed,
+    /// An exact number that can't be stored, like `#e1e99999999` whose
+    /// exponent is too large or `#e+inf.0`
+    OutOfRange,
+}
+
+/// The largest power of ten an exact decimal is scaled by. Exact decimals
+/// are stored as fractions, so the power is computed in full and a huge
+/// exponent would take forever.
+const MAX_EXACT_SCALE: u32 = 10_000;
+
+/// Reads a number literal.
+///
+/// It's an integer `42`, a fraction `3/4` or a decimal like `1.5` or `1e10`,
+/// with an optional sign. It may start with a radix prefix
+/// (`#x`, `#o`, `#b` or `#d`) and an exactness prefix (`#e` or `#i`), in any
+/// order. Decimals must be in radix 10. The infinities and NaN are written
+/// `+inf.0`, `-inf.0` and `+nan.0`, as they are displayed.
+///
+/// # Errors
+///
+/// Fails with [`LiteralError::OutOfRange`] for an exact decimal scaled by
+/// more than 10^10000 or less than 10^-10000, or an exact infinity or NaN,
+/// and with [`LiteralError::Malformed`] for anything that isn't a number.
+pub fn parse_number(s: &str) -> Result<Number, LiteralError> {
+    let mut radix = None;
+    let mut exactness = None;
+    let mut rest = s;
+    while let Some(prefixed) = rest.strip_prefix('#') {
+        let mut chars = prefixed.chars();
+        match chars.next().map(|c| c.to_ascii_lowercase()) {
+            Some('x') if radix.is_none() => radix = Some(16),
+            Some('o') if radix.is_none() => radix = Some(8),
+            Some('b') if radix.is_none() => radix = Some(2),
+            Some('d') if radix.is_none() => radix = Some(10),
+            Some('e') if exactness.is_none() => exactness = Some(Exactness::Exact),
+            Some('i') if exactness.is_none() => exactness = Some(Exactness::Inexact),
+            _ => return Err(LiteralError::Malformed),
+        }
+        rest = chars.as_str();
+    }
+
+    let number = parse_real(rest, radix.unwrap_or(10), exactness)?;
+    return match (exactness, number) {
+        (Some(Exactness::Inexact), number) => Ok(Number::Real(number.to_f64())),
+        (_, number) => Ok(number),
+    };
+}
+
+fn parse_real(s: &str, radix: u32, exactness: Option<Exactness>) -> Result<Number, LiteralError> {
+    let (negative, unsigned) = match s.as_bytes().first() {
+        Some(b'-') => (true, &s[1..]),
+        Some(b'+') => (false, &s[1..]),
+        Some(_) => (false, s),
+        None => return Err(LiteralError::Malformed),
+    };
+    let signed = |n: BigInt| if negative { -n } else { n };
+
+    // They need a sign, so that `inf.0` is still a symbol
+    let special = match unsigned.to_ascii_lowercase().as_str() {
+        "inf.0" if unsigned.len() < s.len() => Some(f64::INFINITY),
+        "nan.0" if unsigned.len() < s.len() => Some(f64::NAN),
+        _ => None,
+    };
+    if let Some(x) = special {
+        if exactness == Some(Exactness::Exact) {
+            return Err(LiteralError::OutOfRange);
+        }
+        return Ok(Number::Real(if negative { -x } else { x }));
+    }
+
+    if let Some((numer, denom)) = unsigned.split_once('/') {
+        let numer = parse_digits(numer, radix).ok_or(LiteralError::Malformed)?;
+        let denom = parse_digits(denom, radix).ok_or(LiteralError::Malformed)?;
+        if denom.is_zero() {
+            return Err(LiteralError::Malformed);
+        }
+
+        return Ok(Number::rational(BigRational::new(signed(numer), denom)));
+    }
+
+    if let Some(n) = parse_digits(unsigned, radix) {
+        return Ok(Number::Integer(signed(n)));
+    }
+
+    if radix != 10 {
+        return Err(LiteralError::Malformed);
+    }
+
+    let (int_digits, frac_digits, exponent) =
+        split_decimal(unsigned).ok_or(LiteralError::Malformed)?;
+    if exactness == Some(Exactness::Exact) {
+        let mantissa = parse_digits(&format!("{int_digits}{frac_digits}"), 10)
+            .ok_or(LiteralError::Malformed)?;
+        let scale = i32::try_from(frac_digits.len())
+            .ok()
+            .and_then(|frac_len| exponent.checked_sub(frac_len))
+            .filter(|scale| scale.unsigned_abs() <= MAX_EXACT_SCALE)
+            .ok_or(LiteralError::OutOfRange)?;
+        let power = BigInt::from(10).pow(scale.unsigned_abs());
+        let ratio = if scale < 0 {
+            BigRational::new(signed(mantissa), power)
+        } else {
+            BigRational::from_integer(signed(mantissa * power))
+        };
+        return Ok(Number::rational(ratio));
+    }
+
+    // The syntax is checked above, the float parser only does the rounding
+    return s
+        .parse()
+        .map(Number::Real)
+        .map_err(|_| LiteralError::Malformed);
+}
+
+/// Digits of the given radix, without sign
+fn parse_digits(s: &str, radix: u32) -> Option<BigInt> {
+    if s.is_empty() || !s.chars().all(|c| c.is_digit(radix)) {
+        return None;
+    }
+
+    return BigInt::parse_bytes(s.as_bytes(), radix);
+}
+
+/// Splits an unsigned decimal like `12.5e-3` into its integer digits,
+/// fraction digits and exponent. It needs a digit before the exponent and
+/// either a `.` or an exponent, or it would be an integer.
+fn split_decimal(s: &str) -> Option<(&str, &str, i32)> {
+    let (mantissa, exponent) = match s.find(['e', 'E']) {
+        Some(idx) => {
+            let exponent = &s[idx + 1..];
+            let digits = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+            if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+                return None;
+            }
+            // One that doesn't fit is out of range anyway, so it's clamped
+            // for the float parser to round to infinity or zero
+            let clamped = if exponent.starts_with('-') {
+                i32::MIN
+            } else {
+                i32::MAX
+            };
+            (&s[..idx], exponent.parse().unwrap_or(clamped))
+        }
+        None => (s, 0),
+    };
+
+    let (int_digits, frac_digits) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let all_digits = |digits: &str| digits.chars().all(|c| c.is_ascii_digit());
+    if int_digits.len() + frac_digits.len() == 0
+        || !all_digits(int_digits)
+        || !all_digits(frac_digits)
+    {
+        return None;
+    }
+
+    return Some((int_digits, frac_digits, exponent));
+}
+
+#[test]
+fn parses_number_literals() {
+    let int = |n: i64| Ok(Number::from(n));
+    let ratio = |n: i64, d: i64| Ok(Number::rational(BigRational::new(n.into(), d.into())));
+
+    assert_eq!(parse_number("42"), int(42));
+    assert_eq!(parse_number("-42"), int(-42));
+    assert_eq!(parse_number("+7"), int(7));
+    assert_eq!(parse_number("3/4"), ratio(3, 4));
+    assert_eq!(parse_number("-6/8"), ratio(-3, 4));
+    assert_eq!(parse_number("4/2"), int(2));
+    assert_eq!(parse_number("1.5"), Ok(Number::Real(1.5)));
+    assert_eq!(parse_number(".5"), Ok(Number::Real(0.5)));
+    assert_eq!(parse_number("1e10"), Ok(Number::Real(1e10)));
+    assert_eq!(parse_number("-2.5E-3"), Ok(Number::Real(-2.5e-3)));
+    assert_eq!(parse_number("#x1F"), int(31));
+    assert_eq!(parse_number("#b101"), int(5));
+    assert_eq!(parse_number("#o-17"), int(-15));
+    assert_eq!(parse_number("#e1.25"), ratio(5, 4));
+    assert_eq!(parse_number("#i3/4"), Ok(Number::Real(0.75)));
+    assert_eq!(parse_number("#x#e10"), int(16));
+    assert_eq!(
+        parse_number("123456789012345678901234567890"),
+        Ok(Number::Integer(
+            "123456789012345678901234567890".parse().unwrap()
+        ))
+    );
+
+    for bad in [
+        "", "-", ".", "1/0", "1.2.3", "1e", "e5", "inf", "nan", "#x1.5", "#b2", "#e#e1", "12abc",
+    ] {
+        assert_eq!(parse_number(bad), Err(LiteralError::Malformed), "{bad}");
+    }
+
+    assert_eq!(parse_number("-inf.0"), Ok(Number::Real(f64::NEG_INFINITY)));
+    assert_eq!(
+        parse_number("1e99999999999"),
+        Ok(Number::Real(f64::INFINITY))
+    );
+    assert_eq!(parse_number("1e-99999999999"), Ok(Number::Real(0.0)));
+    assert_eq!(parse_number("inf.0"), Err(LiteralError::Malformed));
+
+    for huge in [
+        "#e1e99999999",
+        "#e1e-99999999",
+        "#e1e10001",
+        "#e1e99999999999",
+        "#e+inf.0",
+    ] {
+        assert_eq!(parse_number(huge), Err(LiteralError::OutOfRange), "{huge}");
+    }
+    assert!(parse_number("#e1e10000").is_ok());
+}
+
+#[test]
+fn arithmetic_promotes() {
+    let ratio = |n: i64, d: i64| Number::rational(BigRational::new(n.into(), d.into()));
+
+    assert_eq!(Number::from(1) + Number::from(2), Number::from(3));
+    assert_eq!(
+        Number::from(1).checked_div(Number::from(3)),
+        Ok(ratio(1, 3))
+    );
+    assert_eq!(ratio(1, 2) + ratio(1, 2), Number::from(1));
+    assert_eq!(Number::from(1) + Number::Real(0.5), Number::Real(1.5));
+    assert_eq!(ratio(1, 4) * Number::Real(2.0), Number::Real(0.5));
+    assert_eq!(
+        Number::from(-7).modulo(Number::from(2)),
+        Ok(Number::from(1))
+    );
+    assert_eq!(
+        ratio(1, 2).modulo(Number::from(2)),
+        Err(NumericError::NotAnInteger)
+    );
+    assert_eq!(
+        Number::from(1).modulo(Number::Real(0.0)),
+        Err(NumericError::DivisionByZero)
+    );
+    assert_eq!(
+        Number::from(1).checked_div(Number::from(0)),
+        Err(NumericError::DivisionByZero)
+    );
+    assert_eq!(
+        Number::Real(1.0).checked_div(Number::from(0)),
+        Ok(Number::Real(f64::INFINITY))
+    );
+    assert_eq!(
+        Number::from(1).numeric_cmp(&Number::Real(1.0)),
+        Some(Ordering::Equal)
+    );
+    assert_eq!(
+        ratio(1, 3).numeric_cmp(&Number::Real(0.3)),
+        Some(Ordering::Greater)
+    );
+}
+
+#[test]
+fn displays_numbers() {
+    assert_eq!(Number::from(-3).to_string(), "-3");
+    assert_eq!(
+        Number::rational(BigRational::new(3.into(), 4.into())).to_string(),
+        "3/4"
+    );
+    assert_eq!(Number::Real(2.0).to_string(), "2.0");
+    assert_eq!(Number::Real(f64::INFINITY).to_string(), "+inf.0");
+
+    let reals = [f64::INFINITY, f64::NEG_INFINITY, f64::NAN, 2.0, -0.5, 1e300];
+    for x in reals {
+        let shown = Number::Real(x).to_string();
+        assert_eq!(parse_number(&shown), Ok(Number::Real(x)), "{shown}");
+    }
+}
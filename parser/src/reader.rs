@@ -0,0 +1,381 @@
+use std::{
+    fmt::Display,
+    io::{self, BufRead, BufReader, Read},
+    str,
+};
+
+use lalrpop_util::ParseError;
+
+use crate::{
+    ast::{Node, SpannedNode},
+    diagnostics::Diagnostic,
+    lexer::{Lexer, LexicalError, Tok},
+    parse_ceceo_spanned, CeceoParseError,
+};
+
+/// Reads the top-level data of a program one at a time from a byte stream,
+/// so an input too large for memory can still be processed. Only the datum
+/// being read is kept in memory.
+///
+/// Spans are byte offsets from the start of the stream. After an error the
+/// reader stops, as the rest of the input can't be trusted to line up.
+pub struct Reader<R> {
+    source: R,
+    /// Text read from the source, starting at byte `offset` of the stream
+    buffer: String,
+    offset: usize,
+    /// How much of `buffer` was already returned as data
+    consumed: usize,
+    /// The first bytes of a char split between two reads
+    partial_char: Vec<u8>,
+    at_eof: bool,
+    failed: bool,
+}
+
+#[derive(Debug)]
+pub enum ReadError {
+    Io(io::Error),
+    Syntax(Diagnostic),
+}
+
+impl Display for ReadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{err}"),
+            Self::Syntax(diagnostic) => write!(
+                f,
+                "{} at bytes {}..{}",
+                diagnostic.message, diagnostic.span.start, diagnostic.span.end
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Syntax(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for ReadError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+/// Why [`read_datum`] didn't return a datum
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReadDatumError<'input> {
+    /// Only whitespace and comments
+    Empty,
+    /// The datum isn't finished, like `(a (b)` or `'`. It may be completed
+    /// by more input, like the next line typed into a REPL.
+    Incomplete,
+    /// No matter what follows, this isn't a datum
+    Malformed(CeceoParseError<'input>),
+}
+
+/// Reads the first datum of `input`, returning it with the byte offset
+/// where it ends, so the rest of the input can be read by calling this again
+/// on `&input[end..]`.
+///
+/// A datum cut short by the end of `input` is reported as
+/// [`ReadDatumError::Incomplete`] rather than as a syntax error.
+pub fn read_datum(input: &str) -> Result<(Node, usize), ReadDatumError<'_>> {
+    let end = match scan_datum(input, true) {
+        Scan::Datum(end) => end,
+        Scan::Empty => return Err(ReadDatumError::Empty),
+        Scan::Incomplete => return Err(ReadDatumError::Incomplete),
+    };
+
+    match parse_ceceo_spanned(&input[..end]) {
+        Ok(nodes) => nodes
+            .first()
+            .map(|node| (node.to_node(), end))
+            .ok_or(ReadDatumError::Empty),
+        Err(err) if is_incomplete(&err, end) => Err(ReadDatumError::Incomplete),
+        Err(err) => Err(ReadDatumError::Malformed(err)),
+    }
+}
+
+/// Errors caused by the input ending in the middle of a datum
+const fn is_incomplete(err: &CeceoParseError<'_>, input_len: usize) -> bool {
+    match err {
+        ParseError::UnrecognizedEOF { .. }
+        | ParseError::User {
+            error:
+                LexicalError::UnterminatedString { .. } | LexicalError::UnterminatedBlockComment { .. },
+        } => true,
+        ParseError::User {
+            error: LexicalError::MissingCommentedDatum { span },
+        } => span.end == input_len,
+        _ => false,
+    }
+}
+
+/// Where the first datum of a text ends
+enum Scan {
+    Datum(usize),
+    /// The datum, or a token in it, may go on in the text that follows
+    Incomplete,
+    /// Only whitespace and comments
+    Empty,
+}
+
+impl<R: BufRead> Reader<R> {
+    pub const fn new(source: R) -> Self {
+        Self {
+            source,
+            buffer: String::new(),
+            offset: 0,
+            consumed: 0,
+            partial_char: Vec::new(),
+            at_eof: false,
+            failed: false,
+        }
+    }
+
+    /// Appends more of the stream to the buffer: at least as much as the
+    /// text still waiting there, so a long datum is scanned a few times
+    /// rather than once per read.
+    fn fill(&mut self) -> io::Result<()> {
+        self.buffer.drain(..self.consumed);
+        self.offset += self.consumed;
+        self.consumed = 0;
+
+        let mut bytes = std::mem::take(&mut self.partial_char);
+        let wanted = bytes.len() + self.buffer.len().max(1);
+        while bytes.len() < wanted {
+            let chunk = match self.source.fill_buf() {
+                Ok(chunk) => chunk,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            };
+            if chunk.is_empty() {
+                self.at_eof = true;
+                break;
+            }
+
+            bytes.extend_from_slice(chunk);
+            let len = chunk.len();
+            self.source.consume(len);
+        }
+
+        let valid = match str::from_utf8(&bytes) {
+            Ok(text) => text,
+            // A char cut in two by the read is completed by the next one
+            Err(err) if err.error_len().is_none() && !self.at_eof => {
+                let (valid, partial) = bytes.split_at(err.valid_up_to());
+                self.partial_char = partial.to_vec();
+                str::from_utf8(valid).expect("checked by from_utf8")
+            }
+            Err(err) => return Err(io::Error::new(io::ErrorKind::InvalidData, err)),
+        };
+        self.buffer.push_str(valid);
+
+        return Ok(());
+    }
+
+    fn parse(&self, text: &str) -> Result<SpannedNode, ReadError> {
+        let start = self.offset + self.consumed;
+        match parse_ceceo_spanned(text) {
+            Ok(nodes) => {
+                let node = nodes
+                    .into_iter()
+                    .next()
+                    .expect("a scanned datum has a token");
+                Ok(node.shifted(start))
+            }
+            Err(err) => Err(ReadError::Syntax(
+                Diagnostic::from_parse_error(&err).shifted(start),
+            )),
+        }
+    }
+}
+
+impl<R: Read> Reader<BufReader<R>> {
+    pub fn from_read(source: R) -> Self {
+        Self::new(BufReader::new(source))
+    }
+}
+
+impl<R: BufRead> Iterator for Reader<R> {
+    type Item = Result<SpannedNode, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.failed {
+            let pending = &self.buffer[self.consumed..];
+            match scan_datum(pending, self.at_eof) {
+                Scan::Datum(end) => {
+                    let result = self.parse(&pending[..end]);
+                    self.consumed += end;
+                    self.failed = result.is_err();
+                    return Some(result);
+                }
+                Scan::Incomplete => {
+                    if let Err(err) = self.fill() {
+                        self.failed = true;
+                        return Some(Err(err.into()));
+                    }
+                }
+                Scan::Empty => return None,
+            }
+        }
+
+        return None;
+    }
+}
+
+/// Finds the end of the first datum of `input` by matching its brackets.
+/// The datum is only parsed afterwards, so errors are left for the parser
+/// to report, except those that more input could fix.
+fn scan_datum(input: &str, at_eof: bool) -> Scan {
+    let may_continue = |err: &LexicalError| {
+        matches!(
+            err,
+            LexicalError::UnterminatedString { .. } | LexicalError::UnterminatedBlockComment { .. }
+        ) || err.span().end >= input.len()
+    };
+
+    let mut depth = 0_usize;
+    let mut has_tokens = false;
+    for item in Lexer::new(input) {
+        let (_, tok, end) = match item {
+            Ok(token) => token,
+            Err(err) if !at_eof && may_continue(&err) => return Scan::Incomplete,
+            Err(_) => return Scan::Datum(input.len()),
+        };
+        has_tokens = true;
+
+        match tok {
+            Tok::LeftParen(_) | Tok::VectorStart(_) | Tok::BytevectorStart(_) => depth += 1,
+            Tok::RightParen(_) => depth = depth.saturating_sub(1),
+            _ => (),
+        }
+        if depth > 0 || tok.is_prefix() {
+            continue;
+        }
+
+        // `abc` at the end of the input may be the start of `abcd`
+        let may_grow = matches!(
+            tok,
+            Tok::Symbol(_) | Tok::Num(_) | Tok::HashSymbol(_) | Tok::Dot
+        );
+        if may_grow && end == input.len() && !at_eof {
+            return Scan::Incomplete;
+        }
+
+        return Scan::Datum(end);
+    }
+
+    match (at_eof, has_tokens) {
+        // The parser reports the missing closing bracket or datum
+        (true, true) => Scan::Datum(input.len()),
+        (true, false) => Scan::Empty,
+        (false, _) => Scan::Incomplete,
+    }
+}
+
+#[cfg(test)]
+fn read_all(input: &str, capacity: usize) -> Vec<Result<SpannedNode, String>> {
+    let source = BufReader::with_capacity(capacity, input.as_bytes());
+    Reader::new(source)
+        .map(|item| item.map_err(|err| err.to_string()))
+        .collect()
+}
+
+#[test]
+fn reader_matches_parser() {
+    let inputs = [
+        "",
+        "   ; only a comment\n",
+        "(define (f x) (* x 2)) (f 21)",
+        "a b  c",
+        "'(a . b) `(1 ,x ,@y) #(1 2) #u8(3 4)",
+        "\"a string (with parens)\" #\\( #\\) sym",
+        "#| block ( |# (a #;(b c) d) #;e f",
+        "[x {y}] 12345678901234567890 3/4 1.5e3 #xFF",
+        "(λ \"ünïcödé\" #\\λ)",
+        "#; (commented out) kept",
+    ];
+    for input in inputs {
+        let expected: Vec<SpannedNode> = parse_ceceo_spanned(input).unwrap();
+        for capacity in [1, 2, 3, 7, 8 * 1024] {
+            let read: Vec<SpannedNode> = read_all(input, capacity)
+                .into_iter()
+                .map(Result::unwrap)
+                .collect();
+            assert_eq!(read, expected, "{input:?} read {capacity} bytes at a time");
+        }
+    }
+}
+
+#[test]
+fn reader_reports_errors_with_stream_offsets() {
+    let read = read_all("(a b) (c d] (e f)", 4);
+    assert!(read[0].is_ok());
+    assert_eq!(
+        read[1],
+        Err("mismatched brackets: `(` closed by `]` instead of `)` at bytes 10..11".to_string())
+    );
+    // The reader stops after an error
+    assert_eq!(read.len(), 2);
+
+    let read = read_all("1 (unclosed", 2);
+    assert_eq!(read.len(), 2);
+    assert_eq!(
+        read[1],
+        Err("unexpected end of input at bytes 11..11".to_string())
+    );
+
+    let read = read_all("ok \"unterminated", 2);
+    assert_eq!(
+        read[1],
+        Err("unterminated string literal at bytes 3..4".to_string())
+    );
+}
+
+#[test]
+fn reader_reports_invalid_utf8() {
+    let bytes: &[u8] = b"(a) (\xFF)";
+    let mut reader = Reader::new(BufReader::with_capacity(2, bytes));
+    assert!(reader.next().unwrap().is_ok());
+    assert!(matches!(reader.next(), Some(Err(ReadError::Io(_)))));
+    assert!(reader.next().is_none());
+}
+
+#[test]
+fn read_datum_returns_the_rest() {
+    use crate::ast::Atom;
+
+    let input = " (a b) c ; done\n";
+    let (first, end) = read_datum(input).unwrap();
+    assert_eq!(first.to_string(), "(a b)");
+    assert_eq!(end, 6);
+
+    let (second, end) = read_datum(&input[6..]).unwrap();
+    assert_eq!(second, Node::Atom(Atom::Symbol("c".to_string())));
+    assert_eq!(read_datum(&input[6 + end..]), Err(ReadDatumError::Empty));
+}
+
+#[test]
+fn read_datum_tells_incomplete_from_malformed() {
+    for input in [
+        "(a (b)", "'", "`(a ,", "#(1 2", "\"open", "#| open", "#; (a", "(a . ",
+    ] {
+        assert_eq!(
+            read_datum(input),
+            Err(ReadDatumError::Incomplete),
+            "{input:?}"
+        );
+    }
+    for input in [")", "(a]", "(a . b c)", "#;)", "#foo", "(. a)"] {
+        assert!(
+            matches!(read_datum(input), Err(ReadDatumError::Malformed(_))),
+            "{input:?}"
+        );
+    }
+}
@@ -0,0 +1,118 @@
+/// A half-open byte range `start..end` into the parsed source.
+#[derive(Copy, Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    #[must_use]
+    pub const fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// The same span in a text that has `offset` more bytes before it.
+    #[must_use]
+    pub const fn shifted(self, offset: usize) -> Self {
+        Self::new(self.start + offset, self.end + offset)
+    }
+
+    /// Smallest span covering both `self` and `other`.
+    #[must_use]
+    pub fn merge(&self, other: Self) -> Self {
+        Self::new(self.start.min(other.start), self.end.max(other.end))
+    }
+
+    #[must_use]
+    pub fn source_text<'input>(&self, input: &'input str) -> &'input str {
+        &input[self.start..self.end]
+    }
+}
+
+/// A 1-based line and column. Columns count chars, not bytes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct LineCol {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Maps byte offsets of a source string to line/column positions.
+pub struct LineIndex<'input> {
+    input: &'input str,
+    line_starts: Vec<usize>,
+}
+
+impl<'input> LineIndex<'input> {
+    #[must_use]
+    pub fn new(input: &'input str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(input.match_indices('\n').map(|(idx, _)| idx + 1))
+            .collect();
+
+        LineIndex { input, line_starts }
+    }
+
+    #[must_use]
+    pub fn line_col(&self, offset: usize) -> LineCol {
+        let offset = offset.min(self.input.len());
+        let line_idx = match self.line_starts.binary_search(&offset) {
+            Ok(idx) => idx,
+            Err(idx) => idx - 1,
+        };
+        let line_start = self.line_starts[line_idx];
+        let column = self.input[line_start..offset].chars().count() + 1;
+
+        return LineCol {
+            line: line_idx + 1,
+            column,
+        };
+    }
+
+    /// The text of the 1-based `line`, without its line terminator.
+    #[must_use]
+    pub fn line_text(&self, line: usize) -> &'input str {
+        let start = self.line_starts[line - 1];
+        let end = self
+            .line_starts
+            .get(line)
+            .map_or(self.input.len(), |next_start| next_start - 1);
+
+        self.input[start..end].trim_end_matches('\r')
+    }
+
+    #[must_use]
+    pub const fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+}
+
+#[test]
+fn line_col_works() {
+    let source = "(a b)\n(c\n  d)";
+    let index = LineIndex::new(source);
+
+    assert_eq!(index.line_col(0), LineCol { line: 1, column: 1 });
+    assert_eq!(index.line_col(3), LineCol { line: 1, column: 4 });
+    assert_eq!(index.line_col(6), LineCol { line: 2, column: 1 });
+    assert_eq!(index.line_col(11), LineCol { line: 3, column: 3 });
+    assert_eq!(index.line_text(2), "(c");
+    assert_eq!(index.line_count(), 3);
+}
+
+#[test]
+fn line_col_counts_chars() {
+    let source = "(λ x)";
+    let index = LineIndex::new(source);
+
+    assert_eq!(index.line_col(source.find('x').unwrap()).column, 4);
+}
@@ -1,15 +1,19 @@
 #![allow(non_upper_case_globals)]
 
-use parser::ast::{Atom, Node};
-use serde::Serialize;
+use parser::{
+    ast::{Atom, Node},
+    number::Number,
+};
+use serde::{Serialize, Serializer};
 
 #[derive(Clone, Serialize)]
 #[serde(untagged, remote = "Atom")]
 pub enum AtomS {
-    Num(i32),
+    Num(#[serde(serialize_with = "serialize_number")] Number),
     Symbol(String),
     Str(String),
     Bool(bool),
+    Char(char),
 }
 
 #[derive(Serialize)]
@@ -19,9 +23,22 @@ pub enum ListOrAtomInfo {
     Atom(InfoStruct),
 }
 
+/// Exact integers that fit and floats are JSON numbers, the rest are strings
+fn serialize_number<S: Serializer>(number: &Number, serializer: S) -> Result<S::Ok, S::Error> {
+    if let Some(int) = number.as_integer().and_then(|int| i64::try_from(int).ok()) {
+        return serializer.serialize_i64(int);
+    }
+    if let Number::Real(x) = number {
+        return serializer.serialize_f64(*x);
+    }
+
+    return serializer.collect_str(number);
+}
+
 const Num: &str = "number";
 const Symbol: &str = "symbol";
 const Str: &str = "string";
+const Char: &str = "char";
 
 #[derive(Clone, Serialize)]
 pub struct InfoStruct {
@@ -41,12 +58,6 @@ fn nodes_to_info_structs(vn: &[Node]) -> ListOrAtomInfo {
     return ListOrAtomInfo::List(res);
 }
 
-impl ToInfoStruct for Vec<Vec<Node>> {
-    fn to_info_struct(&self) -> ListOrAtomInfo {
-        ListOrAtomInfo::List(self.iter().map(|n| nodes_to_info_structs(n)).collect())
-    }
-}
-
 impl ToInfoStruct for Vec<Node> {
     fn to_info_struct(&self) -> ListOrAtomInfo {
         nodes_to_info_structs(self)
@@ -61,7 +72,7 @@ impl ToInfoStruct for Node {
             Node::Atom(a) | Node::QuoteAtom(a) => match a {
                 Atom::Num(num) => ListOrAtomInfo::Atom(InfoStruct {
                     r#type: Num.to_string(),
-                    value: Box::new(Atom::Num(*num)),
+                    value: Box::new(Atom::Num(num.clone())),
                 }),
                 Atom::Symbol(symbol) => ListOrAtomInfo::Atom(InfoStruct {
                     r#type: Symbol.to_string(),
@@ -75,10 +86,32 @@ impl ToInfoStruct for Node {
                     r#type: b.to_string(),
                     value: Box::new(Atom::Bool(b.to_owned())),
                 }),
+                Atom::Char(c) => ListOrAtomInfo::Atom(InfoStruct {
+                    r#type: Char.to_string(),
+                    value: Box::new(Atom::Char(*c)),
+                }),
             },
             Node::List(list) | Node::QuoteList(list) => {
                 return nodes_to_info_structs(list);
             }
+            Node::DottedList(list, tail) | Node::QuoteDottedList(list, tail) => {
+                let mut items = list.clone();
+                items.push(*tail.clone());
+                return nodes_to_info_structs(&items);
+            }
+            Node::Vector(items) => {
+                return nodes_to_info_structs(items);
+            }
+            Node::Bytevector(bytes) => {
+                let items: Vec<Node> = bytes
+                    .iter()
+                    .map(|byte| Node::Atom(Atom::Num((*byte).into())))
+                    .collect();
+                return nodes_to_info_structs(&items);
+            }
+            Node::Quasiquote(node) | Node::Unquote(node) | Node::UnquoteSplicing(node) => {
+                node.to_info_struct()
+            }
         };
     }
 }